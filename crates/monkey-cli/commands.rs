@@ -0,0 +1,248 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use monkey::ast::{self, Program};
+use monkey::common::Spanned;
+use monkey::compiler::{self, Bytecode, FormatError};
+use monkey::diagnostic::{Diagnostic, JsonDiagnostic, Label};
+use monkey::eval::STACK_SIZE;
+use monkey::lexer::{Lexer, Token};
+use monkey::vm;
+use monkey::{Interpreter, InterpreterError, Object};
+use serde::Serialize;
+
+use crate::input::{report_without_source, Failure, Input};
+use crate::opt::Format;
+
+/// Prints every token with its location, and with `lossless` the whitespace and comments too
+pub fn lex(input: &Input, format: Format, lossless: bool) -> Result<(), Failure> {
+    let tokens = if lossless {
+        Lexer::lossless(input.source())
+    } else {
+        Lexer::new(input.source())
+    };
+    match format {
+        Format::Text => {
+            let file = input.map().get(input.file());
+            for token in tokens {
+                println!("{}\t{:?}", file.location(token.span.start), token.node);
+            }
+        }
+        Format::Json => print_json(&JsonOutput {
+            tokens: Some(tokens.collect()),
+            ..JsonOutput::new(input)
+        }),
+    }
+    Ok(())
+}
+
+/// Prints the syntax tree. In json mode the tree recovered from parse errors is printed too.
+pub fn parse(input: &Input, format: Format) -> Result<(), Failure> {
+    match format {
+        Format::Text => {
+            let program = input.parse()?;
+            println!("{:#?}", program);
+            Ok(())
+        }
+        Format::Json => {
+            let (program, diagnostics) = input.parse_with_diagnostics();
+            print_json(&JsonOutput {
+                program: Some(&program),
+                diagnostics: Some(json_diagnostics(input, &diagnostics)),
+                ..JsonOutput::new(input)
+            });
+            parse_result(&diagnostics)
+        }
+    }
+}
+
+pub fn check(input: &Input, format: Format) -> Result<(), Failure> {
+    match format {
+        Format::Text => input.parse().map(|_| ()),
+        Format::Json => {
+            let (_, diagnostics) = input.parse_with_diagnostics();
+            print_json(&JsonOutput {
+                diagnostics: Some(json_diagnostics(input, &diagnostics)),
+                ..JsonOutput::new(input)
+            });
+            parse_result(&diagnostics)
+        }
+    }
+}
+
+/// Evaluates the script and prints the final value if there is one
+pub fn run(input: &Input) -> Result<(), Failure> {
+    let program = input.parse()?;
+    let mut interpreter = Interpreter::new().with_stack_size(STACK_SIZE);
+    let value = eval(input, &program, &mut interpreter)?;
+    if value != Object::Null {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+/// Runs a script, or a bytecode file from `compile` if the name ends in `.mkc`
+pub fn run_path(path: &Path) -> Result<(), Failure> {
+    if path.extension() == Some(OsStr::new("mkc")) {
+        run_bytecode(path)
+    } else {
+        Input::read(path).and_then(|input| run(&input))
+    }
+}
+
+/// Loads the bytecode and runs it on the vm. The source is not part of the file, so runtime
+/// errors can only name the span of the statement.
+fn run_bytecode(path: &Path) -> Result<(), Failure> {
+    let bytecode = File::open(path)
+        .map_err(FormatError::Io)
+        .and_then(|file| Bytecode::read_from(&mut BufReader::new(file)))
+        .map_err(|err| match err {
+            FormatError::Io(err) => {
+                eprintln!("error: could not read `{}`: {}", path.display(), err);
+                Failure::Io
+            }
+            err => {
+                eprintln!("error: could not load `{}`: {}", path.display(), err);
+                Failure::InvalidBytecode
+            }
+        })?;
+
+    let mut vm = vm::Vm::new(&bytecode);
+    match vm.run() {
+        Object::Error(message) => {
+            let mut diagnostic = Diagnostic::error(message);
+            if let Some(span) = vm.error_span() {
+                diagnostic = diagnostic.with_note(format!("in the statement at bytes {}", span));
+            }
+            report_without_source(&diagnostic);
+            Err(Failure::Runtime)
+        }
+        Object::Null => Ok(()),
+        value => {
+            println!("{}", value);
+            Ok(())
+        }
+    }
+}
+
+/// Compiles the script to a bytecode file
+pub fn compile_file(path: &Path, output: &Path) -> Result<(), Failure> {
+    let input = Input::read(path)?;
+    let program = input.parse()?;
+    let bytecode = compile(&input, &program)?;
+    File::create(output)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            bytecode.write_to(&mut writer)?;
+            writer.flush()
+        })
+        .map_err(|err| {
+            eprintln!("error: could not write `{}`: {}", output.display(), err);
+            Failure::Io
+        })
+}
+
+/// Prints the bytecode listing, with the source line of every statement
+pub fn disasm(input: &Input) -> Result<(), Failure> {
+    let program = input.parse()?;
+    let bytecode = compile(input, &program)?;
+    let file = input.map().get(input.file());
+    print!("{}", compiler::disassemble(&bytecode, Some(file)));
+    Ok(())
+}
+
+/// Prints the canonical form of the script, or with `check` only reports whether it differs
+pub fn fmt(input: &Input, check: bool) -> Result<(), Failure> {
+    let program = input.parse()?;
+    let formatted = ast::pretty_with_comments(&program, input.source()).map_err(|err| {
+        let label = Label::primary(input.file(), err.span).with_message("this comment");
+        input.report(&Diagnostic::error(err.to_string()).with_label(label));
+        Failure::Unformatted
+    })?;
+    if !check {
+        print!("{}", formatted);
+        Ok(())
+    } else if formatted == input.source() {
+        Ok(())
+    } else {
+        eprintln!("{} is not formatted", input.name());
+        Err(Failure::Unformatted)
+    }
+}
+
+/// Evaluates the program, reporting a runtime error at the statement that caused it
+pub fn eval(
+    input: &Input,
+    program: &Program,
+    interpreter: &mut Interpreter,
+) -> Result<Object, Failure> {
+    interpreter.eval_program_in(input.file(), program).map_err(|err| {
+        let mut diagnostic = Diagnostic::error(err.to_string());
+        // the error can be in a function that an earlier input of the REPL defined
+        if let InterpreterError::Runtime {
+            span: Some(span),
+            file: Some(file),
+            ..
+        } = err
+        {
+            diagnostic = diagnostic.with_label(Label::primary(file, span));
+        }
+        input.report(&diagnostic);
+        Failure::Runtime
+    })
+}
+
+pub fn compile(input: &Input, program: &Program) -> Result<Bytecode, Failure> {
+    compiler::compile(program).map_err(|err| {
+        input.report(&Diagnostic::error(err.to_string()));
+        Failure::Compile
+    })
+}
+
+/// The json document printed for every input, see docs/json.md
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    file: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokens: Option<Vec<Spanned<Token<'a>>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    program: Option<&'a Program>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostics: Option<Vec<JsonDiagnostic<'a>>>,
+}
+
+impl<'a> JsonOutput<'a> {
+    fn new(input: &'a Input) -> JsonOutput<'a> {
+        JsonOutput {
+            file: input.name(),
+            tokens: None,
+            program: None,
+            diagnostics: None,
+        }
+    }
+}
+
+/// Prints one json document per line
+fn print_json(output: &JsonOutput) {
+    println!(
+        "{}",
+        serde_json::to_string(output).expect("serializing to a string cannot fail")
+    );
+}
+
+fn json_diagnostics<'a>(input: &'a Input, diagnostics: &'a [Diagnostic]) -> Vec<JsonDiagnostic<'a>> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_json(input.map()))
+        .collect()
+}
+
+fn parse_result(diagnostics: &[Diagnostic]) -> Result<(), Failure> {
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(Failure::Parse)
+    }
+}
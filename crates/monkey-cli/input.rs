@@ -0,0 +1,163 @@
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::{fs, process};
+
+use monkey::ast::Program;
+use monkey::common::{FileId, SourceMap};
+use monkey::diagnostic::{Diagnostic, Renderer, ToDiagnostic};
+use monkey::parser::parse_with_errors;
+
+/// Exit codes, taken from sysexits.h
+pub const EXIT_PARSE_ERROR: i32 = 65;
+pub const EXIT_COMPILE_ERROR: i32 = 65;
+pub const EXIT_INVALID_BYTECODE: i32 = 65;
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+pub const EXIT_IO_ERROR: i32 = 74;
+pub const EXIT_UNFORMATTED: i32 = 1;
+
+/// Why a command failed. The details have already been reported by the time this is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    Io,
+    Parse,
+    Compile,
+    InvalidBytecode,
+    Runtime,
+    Unformatted,
+}
+
+impl Failure {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Failure::Io => EXIT_IO_ERROR,
+            Failure::Parse => EXIT_PARSE_ERROR,
+            Failure::Compile => EXIT_COMPILE_ERROR,
+            Failure::InvalidBytecode => EXIT_INVALID_BYTECODE,
+            Failure::Runtime => EXIT_RUNTIME_ERROR,
+            Failure::Unformatted => EXIT_UNFORMATTED,
+        }
+    }
+
+    pub fn exit(self) -> ! {
+        process::exit(self.exit_code())
+    }
+}
+
+/// Runs `f` on every path, or on `-` for stdin if there are none. Keeps going after a failure and
+/// returns the first one.
+pub fn for_each_path(
+    paths: &[PathBuf],
+    mut f: impl FnMut(&Path) -> Result<(), Failure>,
+) -> Result<(), Failure> {
+    let stdin = [PathBuf::from("-")];
+    let paths = if paths.is_empty() { &stdin[..] } else { paths };
+    let mut first_failure = None;
+    for path in paths {
+        if let Err(failure) = f(path) {
+            first_failure.get_or_insert(failure);
+        }
+    }
+    first_failure.map_or(Ok(()), Err)
+}
+
+/// Like `for_each_path`, with every file read as a script
+pub fn for_each_input(
+    paths: &[PathBuf],
+    mut f: impl FnMut(&Input) -> Result<(), Failure>,
+) -> Result<(), Failure> {
+    for_each_path(paths, |path| Input::read(path).and_then(|input| f(&input)))
+}
+
+/// A source file loaded into its own source map so diagnostics can point into it. More sources
+/// can be added, which keeps the earlier ones around for diagnostics.
+pub struct Input {
+    map: SourceMap,
+    file: FileId,
+}
+
+impl Input {
+    /// Reads the file at `path`, or stdin if the path is `-`
+    pub fn read(path: &Path) -> Result<Input, Failure> {
+        let res = if path == Path::new("-") {
+            let mut source = String::new();
+            io::stdin()
+                .read_to_string(&mut source)
+                .map(|_| ("<stdin>".to_string(), source))
+        } else {
+            fs::read_to_string(path).map(|source| (path.display().to_string(), source))
+        };
+        let (name, source) = res.map_err(|err| {
+            eprintln!("error: could not read `{}`: {}", path.display(), err);
+            Failure::Io
+        })?;
+        Ok(Input::new(name, source))
+    }
+
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Input {
+        let mut map = SourceMap::new();
+        let file = map.add(name, source);
+        Input { map, file }
+    }
+
+    /// Adds another source and makes it the current file, like the REPL does for every line
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.file = self.map.add(name, source);
+    }
+
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn name(&self) -> &str {
+        self.map.get(self.file).name()
+    }
+
+    pub fn source(&self) -> &str {
+        self.map.get(self.file).source()
+    }
+
+    pub fn map(&self) -> &SourceMap {
+        &self.map
+    }
+
+    /// Parses the source, reporting every parse error
+    pub fn parse(&self) -> Result<Program, Failure> {
+        let (program, diagnostics) = self.parse_with_diagnostics();
+        if diagnostics.is_empty() {
+            return Ok(program);
+        }
+        for diagnostic in &diagnostics {
+            self.report(diagnostic);
+        }
+        Err(Failure::Parse)
+    }
+
+    /// Parses the source, returning the recovered program and a diagnostic for every parse error
+    pub fn parse_with_diagnostics(&self) -> (Program, Vec<Diagnostic>) {
+        let (program, errors) = parse_with_errors(self.source());
+        let diagnostics = errors
+            .iter()
+            .map(|err| err.to_diagnostic(self.file))
+            .collect();
+        (program, diagnostics)
+    }
+
+    /// Renders the diagnostic to stderr
+    pub fn report(&self, diagnostic: &Diagnostic) {
+        eprint!("{}", stderr_renderer().render(&self.map, diagnostic));
+    }
+}
+
+/// Renders a diagnostic without labels to stderr, for errors that are not about any source
+pub fn report_without_source(diagnostic: &Diagnostic) {
+    eprint!("{}", stderr_renderer().render(&SourceMap::new(), diagnostic));
+}
+
+/// Renderer for stderr, colored only when it is a terminal
+fn stderr_renderer() -> Renderer {
+    if io::stderr().is_terminal() {
+        Renderer::colored()
+    } else {
+        Renderer::plain()
+    }
+}
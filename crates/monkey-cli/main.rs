@@ -1,52 +1,56 @@
+mod commands;
+mod input;
 mod opt;
+mod repl;
 
-use std::sync::mpsc::channel;
+use std::panic;
+use std::thread;
 
 use clap::Clap;
-use crossbeam_utils::thread;
-use monkey::lexer::Lexer;
-// use lexer::Lexer;
-use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use monkey::eval::STACK_SIZE;
 
-use opt::Opt;
+use input::{for_each_input, for_each_path};
+use opt::{Command, Opt};
 
 fn main() {
     env_logger::init();
 
+    // the evaluator recurses on the native stack, the commands tell it how large this one is
+    let commands = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("could not start the interpreter thread");
+    if let Err(panic) = commands.join() {
+        panic::resume_unwind(panic);
+    }
+}
+
+fn run() {
     let opt: Opt = Opt::parse();
 
-    match opt.file_path {
-        Some(path) => panic!("no implemented yet"),
-        None => {
-            let mut rl = Editor::<()>::new();
-            if rl.load_history("history.txt").is_err() {
-                println!("No previous history.")
-            }
-            loop {
-                let readline = rl.readline(">> ");
-                match readline {
-                    Ok(line) => {
-                        rl.add_history_entry(line.as_str());
-                        for token in Lexer::new(&line) {
-                            println!("{:?}", token);
-                        }
-                    }
-                    Err(ReadlineError::Interrupted) => {
-                        println!("CTRL-C");
-                        break;
-                    }
-                    Err(ReadlineError::Eof) => {
-                        println!("CTRL-D");
-                        break;
-                    }
-                    Err(err) => {
-                        println!("Error: {:?}", err);
-                        break;
-                    }
-                }
-            }
-            rl.save_history("history.txt").unwrap();
+    let res = match (opt.command, opt.file_path) {
+        (Some(Command::Lex(opt)), _) => for_each_input(&opt.output.files.files, |input| {
+            commands::lex(input, opt.output.format, opt.lossless)
+        }),
+        (Some(Command::Parse(opt)), _) => {
+            for_each_input(&opt.files.files, |input| commands::parse(input, opt.format))
+        }
+        (Some(Command::Check(opt)), _) => {
+            for_each_input(&opt.files.files, |input| commands::check(input, opt.format))
+        }
+        (Some(Command::Run(opt)), _) => for_each_path(&opt.files, commands::run_path),
+        (Some(Command::Compile(opt)), _) => commands::compile_file(&opt.file, &opt.output),
+        (Some(Command::Disasm(opt)), _) => for_each_input(&opt.files, commands::disasm),
+        (Some(Command::Fmt(opt)), _) => {
+            for_each_input(&opt.files.files, |input| commands::fmt(input, opt.check))
+        }
+        (Some(Command::Repl), _) | (None, None) => {
+            repl::repl();
+            Ok(())
         }
+        (None, Some(path)) => commands::run_path(&path),
+    };
+    if let Err(failure) = res {
+        failure.exit();
     }
 }
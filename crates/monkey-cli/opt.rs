@@ -1,8 +1,98 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use clap::Clap;
+use clap::{AppSettings, Clap};
 
 #[derive(Clap)]
+#[clap(setting = AppSettings::ArgsNegateSubcommands)]
 pub struct Opt {
-    pub file_path: Option<PathBuf>
+    /// Script to run, or `-` to read it from stdin. Starts a repl if left out
+    pub file_path: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clap)]
+pub enum Command {
+    /// Print the token stream
+    Lex(LexOpt),
+    /// Print the syntax tree
+    Parse(OutputOpt),
+    /// Report diagnostics without running anything
+    Check(OutputOpt),
+    /// Evaluate scripts and print their final values. Files ending in `.mkc` are run as bytecode
+    Run(Files),
+    /// Compile a script to a bytecode file for `run`
+    Compile(CompileOpt),
+    /// Print the bytecode that scripts compile to
+    Disasm(Files),
+    /// Print scripts in canonical form
+    Fmt(FmtOpt),
+    /// Start an interactive session
+    Repl,
+}
+
+#[derive(Clap)]
+pub struct Files {
+    /// Files to read, `-` for stdin. Reads stdin if none are given
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Clap)]
+pub struct CompileOpt {
+    /// Where to write the bytecode, usually a `.mkc` file
+    #[clap(short, long)]
+    pub output: PathBuf,
+
+    /// Script to compile, `-` for stdin
+    pub file: PathBuf,
+}
+
+#[derive(Clap)]
+pub struct OutputOpt {
+    /// `text` for people or `json` for tools, see docs/json.md for the schema
+    #[clap(long, default_value = "text", possible_values = &["text", "json"])]
+    pub format: Format,
+
+    #[clap(flatten)]
+    pub files: Files,
+}
+
+#[derive(Clap)]
+pub struct LexOpt {
+    /// Also print whitespace and comments, so that the token texts add up to the input
+    #[clap(long)]
+    pub lossless: bool,
+
+    #[clap(flatten)]
+    pub output: OutputOpt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format `{}`", s)),
+        }
+    }
+}
+
+#[derive(Clap)]
+pub struct FmtOpt {
+    /// Only check that the files are formatted, exiting with 1 if they are not
+    #[clap(long)]
+    pub check: bool,
+
+    #[clap(flatten)]
+    pub files: Files,
 }
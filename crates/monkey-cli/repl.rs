@@ -0,0 +1,48 @@
+use monkey::eval::STACK_SIZE;
+use monkey::{Interpreter, Object};
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+
+use crate::commands;
+use crate::input::Input;
+
+/// Reads lines and evaluates them until ctrl-c or ctrl-d. Bindings persist between lines.
+pub fn repl() {
+    let mut rl = Editor::<()>::new();
+    if rl.load_history("history.txt").is_err() {
+        println!("No previous history.")
+    }
+    let mut interpreter = Interpreter::new().with_stack_size(STACK_SIZE);
+    // every line is added to the same input, so errors in functions from earlier lines can still
+    // point at them
+    let mut input = Input::new("<repl>", "");
+    loop {
+        let readline = rl.readline(">> ");
+        match readline {
+            Ok(line) => {
+                rl.add_history_entry(line.as_str());
+                input.add("<repl>", line);
+                let value = input
+                    .parse()
+                    .and_then(|program| commands::eval(&input, &program, &mut interpreter));
+                match value {
+                    Ok(Object::Null) | Err(_) => {}
+                    Ok(value) => println!("{}", value),
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                break;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break;
+            }
+        }
+    }
+    rl.save_history("history.txt").unwrap();
+}
@@ -1,4 +0,0 @@
-#[test]
-fn tests() {
-    let t = trybuild::TestCases::new();
-}
@@ -0,0 +1,41 @@
+use std::fmt;
+
+use crate::common::Spanned;
+use crate::lexer::Token;
+use crate::parser::{Parse, ParseResult, Parser};
+
+use super::Statement;
+
+/// Statements surrounded by braces, used as the body of functions and if expressions
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BlockStatement {
+    pub statements: Vec<Spanned<Statement>>,
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.statements.is_empty() {
+            return write!(f, "{{}}");
+        }
+        write!(f, "{{ ")?;
+        for stmt in &self.statements {
+            write!(f, "{} ", stmt)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl Parse for BlockStatement {
+    fn parse(p: &mut Parser) -> ParseResult<Self> {
+        p.expect(Token::Lbrace)?;
+        p.nested(|p| {
+            let mut block = BlockStatement::default();
+            while p.peek_or_err()?.node != Token::Rbrace {
+                block.statements.push(Statement::parse_or_recover(p));
+            }
+            p.next_or_err()?;
+            Ok(block)
+        })
+    }
+}
@@ -1,46 +1,316 @@
 use std::fmt;
 
-use crate::parser::{Parse, ParseError, ParseResult, Parser};
-use crate::lexer::Token;
+use log::debug;
+
+use crate::common::{Peekable, Span, Spanned};
+use crate::lexer::{self, Token};
+use crate::parser::{Parse, ParseError, ParseResult, Parser, Precedence};
+
+use super::{BlockStatement, Statement};
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Expression<'a> {
-    // Infix {
-    //     lhs: Box<Expression<'a>>,
-    //     operator: Token<'a>,
-    //     rhs: Box<Expression<'a>>,
-    // },
-    // Prefix {
-    //     prefix: Token<'a>,
-    //     rhs: Box<Expression<'a>>,
-    // },
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", content = "data")
+)]
+pub enum Expression {
+    Infix {
+        lhs: Box<Expression>,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_operator"))]
+        operator: Token<'static>,
+        rhs: Box<Expression>,
+    },
+    /// `x = value`, or a compound assignment like `x += value`
+    Assign {
+        ident: String,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_operator"))]
+        operator: Token<'static>,
+        value: Box<Expression>,
+    },
+    Prefix {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize_operator"))]
+        prefix: Token<'static>,
+        rhs: Box<Expression>,
+    },
+    If {
+        condition: Box<Expression>,
+        consequence: BlockStatement,
+        alternative: Option<BlockStatement>,
+    },
+    Function {
+        params: Vec<String>,
+        body: BlockStatement,
+    },
+    Call {
+        function: Box<Expression>,
+        args: Vec<Expression>,
+        /// from the start of the function expression to the closing parenthesis
+        span: Span,
+    },
+    Array(Vec<Expression>),
+    Index {
+        left: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// key value pairs in the order they were written
+    Hash(Vec<(Expression, Expression)>),
+    Identifier(String),
     NumberLiteral(i64),
+    FloatLiteral(f64),
     BooleanLiteral(bool),
-    Phantom(std::marker::PhantomData<&'a str>),
+    /// the decoded value of a string literal
+    StringLiteral(String),
 }
 
-impl<'a> fmt::Display for Expression<'a> {
+impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            // Expression::Infix { lhs, operator, rhs } => write!(f, "({} {} {})", lhs, operator, rhs),
-            // Expression::Prefix { prefix, rhs } => write!(f, "({}{})", prefix, rhs),
+            Expression::Infix { lhs, operator, rhs } => write!(f, "({} {} {})", lhs, operator, rhs),
+            Expression::Assign {
+                ident,
+                operator,
+                value,
+            } => write!(f, "({} {} {})", ident, operator, value),
+            Expression::Prefix { prefix, rhs } => write!(f, "({}{})", prefix, rhs),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                write!(f, "if {} {}", condition, consequence)?;
+                if let Some(alternative) = alternative {
+                    write!(f, " else {}", alternative)?;
+                }
+                Ok(())
+            }
+            Expression::Function { params, body } => write!(f, "fn({}) {}", params.join(", "), body),
+            Expression::Call { function, args, .. } => {
+                let args: Vec<_> = args.iter().map(ToString::to_string).collect();
+                write!(f, "{}({})", function, args.join(", "))
+            }
+            Expression::Array(items) => {
+                let items: Vec<_> = items.iter().map(ToString::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Expression::Index { left, index } => write!(f, "({}[{}])", left, index),
+            Expression::Hash(pairs) => {
+                let pairs: Vec<_> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Expression::Identifier(x) => write!(f, "{}", x),
             Expression::NumberLiteral(x) => write!(f, "{}", x),
+            // debug formatting always includes a `.` or an exponent, so it lexes as a float again
+            Expression::FloatLiteral(x) => write!(f, "{:?}", x),
             Expression::BooleanLiteral(x) => write!(f, "{}", x),
-            _ => todo!(),
+            Expression::StringLiteral(x) => write!(f, "\"{}\"", lexer::escape(x)),
         }
     }
 }
 
-impl Parse for Expression<'_> {
+impl Parse for Expression {
     fn parse(p: &mut Parser) -> ParseResult<Self> {
-        let next = p.next_or_err()?;
-        println!("Next: {:?}", next);
+        Expression::parse_precedence(p, Precedence::Lowest)
+    }
+}
+
+impl Expression {
+    /// Pratt parser loop. Keeps folding infix operators into the left hand side while they bind
+    /// tighter than `precedence`.
+    fn parse_precedence(p: &mut Parser, precedence: Precedence) -> ParseResult<Self> {
+        let start = p.lexer.peek_span();
+        let mut lhs = Expression::parse_prefix(p)?;
+
+        while let Some((operator, operator_precedence)) = Expression::next_infix(p, precedence)? {
+            lhs = Expression::parse_infix(p, lhs, operator, operator_precedence, start)?;
+        }
+
+        Ok(lhs)
+    }
+
+    /// Consumes the next token if it is an infix operator that binds tighter than `precedence`
+    fn next_infix(
+        p: &mut Parser,
+        precedence: Precedence,
+    ) -> ParseResult<Option<(Token<'static>, Precedence)>> {
+        let operator = match p.lexer().peek() {
+            Some(token) => token.node,
+            None => return Ok(None),
+        };
+        if operator == Token::Bang {
+            return Err(ParseError::BadPostfixOperator {
+                op: operator.to_string(),
+            });
+        }
+
+        match (operator.to_static(), Precedence::of_infix(&operator)) {
+            (Some(operator), Some(operator_precedence)) if precedence < operator_precedence => {
+                p.next_or_err()?;
+                Ok(Some((operator, operator_precedence)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Folds the infix `operator`, which was just consumed, and its right hand side into `lhs`
+    fn parse_infix(
+        p: &mut Parser,
+        lhs: Expression,
+        operator: Token<'static>,
+        operator_precedence: Precedence,
+        start: Span,
+    ) -> ParseResult<Self> {
+        Ok(match operator {
+            Token::Lparen => Expression::Call {
+                function: Box::new(lhs),
+                args: p.parse_delimited(Token::Rparen, Parser::parse)?,
+                span: start.to(p.lexer.curr_span()),
+            },
+            _ if operator_precedence == Precedence::Assign => {
+                let ident = match lhs {
+                    Expression::Identifier(ident) => ident,
+                    target => {
+                        return Err(ParseError::InvalidAssignmentTarget {
+                            target: target.to_string(),
+                        })
+                    }
+                };
+                // assignment is right associative, so the value can be another assignment
+                Expression::Assign {
+                    ident,
+                    operator,
+                    value: Box::new(p.nested(Parser::parse)?),
+                }
+            }
+            Token::Lbracket => {
+                let index = p.nested(Parser::parse)?;
+                p.expect(Token::Rbracket)?;
+                Expression::Index {
+                    left: Box::new(lhs),
+                    index: Box::new(index),
+                }
+            }
+            _ => Expression::Infix {
+                lhs: Box::new(lhs),
+                operator,
+                rhs: Box::new(Expression::parse_precedence(p, operator_precedence)?),
+            },
+        })
+    }
+
+    fn parse_prefix_operator(p: &mut Parser, prefix: Token<'static>) -> ParseResult<Self> {
+        Ok(Expression::Prefix {
+            prefix,
+            rhs: Box::new(p.nested(|p| Expression::parse_precedence(p, Precedence::Prefix))?),
+        })
+    }
+
+    fn parse_group(p: &mut Parser) -> ParseResult<Self> {
+        let expr = p.nested(Parser::parse)?;
+        p.expect(Token::Rparen)?;
+        Ok(expr)
+    }
+
+    fn parse_if(p: &mut Parser) -> ParseResult<Self> {
+        let condition = Box::new(p.parse()?);
+        let consequence = p.parse()?;
+        let alternative = if p.peek_is(Token::Else) {
+            Some(Expression::parse_else(p)?)
+        } else {
+            None
+        };
+        Ok(Expression::If {
+            condition,
+            consequence,
+            alternative,
+        })
+    }
+
+    /// Parses `else` and the block after it. `else if` is short for a block holding just the next
+    /// `if`.
+    fn parse_else(p: &mut Parser) -> ParseResult<BlockStatement> {
+        p.expect(Token::Else)?;
+        if !p.peek_is(Token::If) {
+            return p.parse();
+        }
+        let start = p.lexer.peek_span();
+        let expr = p.nested(|p| {
+            p.next_or_err()?;
+            Expression::parse_if(p)
+        })?;
+        let span = start.to(p.lexer.curr_span());
+        Ok(BlockStatement {
+            statements: vec![Spanned::new(Statement::Expression(expr), span)],
+        })
+    }
+
+    fn parse_function(p: &mut Parser) -> ParseResult<Self> {
+        p.expect(Token::Lparen)?;
+        let params = p.parse_delimited(Token::Rparen, |p| {
+            p.expect_ident().map(ToString::to_string)
+        })?;
+        p.parse().map(|body| Expression::Function { params, body })
+    }
+
+    fn parse_hash(p: &mut Parser) -> ParseResult<Self> {
+        let pairs = p.parse_delimited(Token::Rbrace, |p| {
+            let key = p.parse()?;
+            p.expect(Token::Colon)?;
+            Ok((key, p.parse()?))
+        })?;
+        Ok(Expression::Hash(pairs))
+    }
+
+    fn parse_prefix(p: &mut Parser) -> ParseResult<Self> {
+        let Spanned { node: next, span } = p.next_or_err()?;
+        debug!("prefix token: {:?}", next);
+
+        match next {
+            Token::Bang => Expression::parse_prefix_operator(p, Token::Bang),
+            Token::Minus => Expression::parse_prefix_operator(p, Token::Minus),
+            Token::Lparen => Expression::parse_group(p),
+            Token::Lbracket => {
+                let items = p.parse_delimited(Token::Rbracket, Parser::parse);
+                items.map(Expression::Array)
+            }
+            Token::Lbrace => Expression::parse_hash(p),
+            Token::If => Expression::parse_if(p),
+            Token::Function => Expression::parse_function(p),
+            _ => Expression::parse_literal(p, Spanned::new(next, span)),
+        }
+    }
 
+    /// Parses the expressions that don't nest, kept apart from `parse_prefix` so that the frames
+    /// of the recursive path stay small
+    fn parse_literal(p: &mut Parser, token: Spanned<Token<'_>>) -> ParseResult<Self> {
+        let Spanned { node: next, span } = token;
         Ok(match next {
-            Token::Number(n) => Expression::NumberLiteral(n.parse::<i64>().map_err(|e| ParseError::BadNumber)?),
+            Token::Int(raw) => Expression::NumberLiteral(lexer::parse_int(raw)?),
+            Token::Float(raw) => Expression::FloatLiteral(lexer::parse_float(raw)?),
+            Token::Str(raw) => match lexer::unescape(raw) {
+                Ok(s) => Expression::StringLiteral(s),
+                Err(err) => {
+                    let err_span = Span::new(span.start + err.span.start, span.start + err.span.end);
+                    return Err(p.error_at(err.node.into(), err_span));
+                }
+            },
             Token::True => Expression::BooleanLiteral(true),
             Token::False => Expression::BooleanLiteral(false),
-            _ => panic!(),
+            Token::Ident(ident) => Expression::Identifier(ident.to_string()),
+            _ if Precedence::of_infix(&next).is_some() => {
+                return Err(ParseError::BadPrefixOperator {
+                    op: next.to_string(),
+                })
+            }
+            Token::Illegal(c) => return Err(ParseError::IllegalCharacter(c.to_string())),
+            _ => {
+                return Err(ParseError::ExpectedExpression {
+                    got: next.to_string(),
+                })
+            }
         })
     }
 }
@@ -1,7 +1,131 @@
+mod block;
 mod expr;
+mod pretty;
 mod program;
 mod stmt;
 
+pub use block::BlockStatement;
 pub use expr::Expression;
+pub use pretty::{pretty, pretty_with_comments, MisplacedComment};
 pub use program::Program;
 pub use stmt::Statement;
+
+/// Operators are serialized as their text, like `"+"`
+#[cfg(feature = "serde")]
+fn serialize_operator<S: serde::Serializer>(
+    operator: &crate::lexer::Token<'static>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(operator.as_str())
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use serde_json::{json, Value};
+
+    use crate::parser::{parse, parse_with_errors};
+
+    #[test]
+    fn program_json() {
+        let program = parse("let x = -1; f(x + 2);").unwrap();
+        let expected = json!({ "statements": [
+            {
+                "kind": "Let",
+                "span": { "start": 0, "end": 11 },
+                "data": {
+                    "ident": "x",
+                    "value": {
+                        "kind": "Prefix",
+                        "data": { "prefix": "-", "rhs": { "kind": "NumberLiteral", "data": 1 } },
+                    },
+                },
+            },
+            {
+                "kind": "Expression",
+                "span": { "start": 12, "end": 21 },
+                "data": {
+                    "kind": "Call",
+                    "data": {
+                        "function": { "kind": "Identifier", "data": "f" },
+                        "args": [{
+                            "kind": "Infix",
+                            "data": {
+                                "lhs": { "kind": "Identifier", "data": "x" },
+                                "operator": "+",
+                                "rhs": { "kind": "NumberLiteral", "data": 2 },
+                            },
+                        }],
+                        "span": { "start": 12, "end": 20 },
+                    },
+                },
+            },
+        ]});
+        assert_eq!(serde_json::to_value(&program).unwrap(), expected);
+    }
+
+    /// The keys of `data` of every kind of node in the tree, `None` if `data` is not an object
+    fn data_keys(value: &Value, keys: &mut BTreeMap<String, Option<BTreeSet<String>>>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::String(kind)) = object.get("kind") {
+                    let data = match object.get("data") {
+                        Some(Value::Object(data)) if !data.contains_key("kind") => {
+                            Some(data.keys().cloned().collect())
+                        }
+                        _ => None,
+                    };
+                    keys.insert(kind.clone(), data);
+                }
+                object.values().for_each(|value| data_keys(value, keys));
+            }
+            Value::Array(items) => items.iter().for_each(|value| data_keys(value, keys)),
+            _ => (),
+        }
+    }
+
+    /// The quoted keys in the data column of the tables of kinds in the docs
+    fn documented_keys() -> BTreeMap<String, Option<BTreeSet<String>>> {
+        let docs = include_str!("../../../../docs/json.md");
+        let mut keys = BTreeMap::new();
+        let mut in_table = false;
+        for line in docs.lines() {
+            if line.contains(" kind ") {
+                in_table = true;
+                continue;
+            }
+            if !line.starts_with('|') {
+                in_table = false;
+            }
+            if !in_table || line.starts_with("|-") {
+                continue;
+            }
+            let columns: Vec<_> = line.split('|').map(str::trim).collect();
+            let kind = columns[1].trim_matches('`').to_string();
+            let data = columns[2].trim_start_matches('`');
+            let fields = if data.starts_with('{') {
+                // every other piece is quoted, it is a key if a colon follows it
+                let pieces: Vec<_> = data.split('"').collect();
+                let keys = (1..pieces.len())
+                    .step_by(2)
+                    .filter(|&idx| pieces.get(idx + 1).is_some_and(|next| next.starts_with(':')));
+                Some(keys.map(|idx| pieces[idx].to_string()).collect())
+            } else {
+                None
+            };
+            keys.insert(kind, fields);
+        }
+        keys
+    }
+
+    #[test]
+    fn json_docs_match_the_tree() {
+        let source = "/// d\nlet x = -1;\nreturn x;\nx += 1;\nif (x) { 1 } else { 2 };\n\
+                      fn(a) { a }(1 + 2);\n[1][0];\n{1: 2.5};\ntrue;\n\"s\";\nlet = ;\n";
+        let (program, _) = parse_with_errors(source);
+        let mut emitted = BTreeMap::new();
+        data_keys(&serde_json::to_value(&program).unwrap(), &mut emitted);
+        assert_eq!(documented_keys(), emitted);
+    }
+}
@@ -0,0 +1,415 @@
+use thiserror::Error;
+
+use crate::common::{Span, Spanned};
+use crate::lexer::{self, Lexer, Token};
+use crate::parser::Precedence;
+
+use super::{BlockStatement, Expression, Program, Statement};
+
+const INDENT: &str = "    ";
+
+/// Prints the program in canonical form: one statement per line, blocks indented by four spaces
+/// and only the parentheses that are needed to parse back to the same tree.
+pub fn pretty(program: &Program) -> String {
+    let mut printer = Printer::default();
+    printer.statements(program.statements());
+    printer.out
+}
+
+/// A comment that `pretty_with_comments` cannot keep in its place
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Comments inside of expressions cannot be formatted")]
+pub struct MisplacedComment {
+    pub span: Span,
+}
+
+/// Like `pretty`, keeping the comments of the source that the program was parsed from. Comments
+/// on the lines before a statement, at the end of its line, or after the last statement of a
+/// block are kept there. Comments anywhere else would have to be moved, so they are an error.
+pub fn pretty_with_comments(program: &Program, source: &str) -> Result<String, MisplacedComment> {
+    let mut printer = Printer {
+        comments: comments(source),
+        ..Printer::default()
+    };
+    printer.statements(program.statements());
+    // comments at the end, or in a program without statements
+    for idx in 0..printer.comments.len() {
+        if printer.comments[idx].next.is_none() {
+            printer.own_line_comment(idx);
+        }
+    }
+    match printer.comments.iter().find(|comment| !comment.printed) {
+        Some(comment) => Err(MisplacedComment { span: comment.span }),
+        None => Ok(printer.out),
+    }
+}
+
+/// A comment of the source, with the tokens around it
+#[derive(Debug)]
+struct Comment<'a> {
+    text: &'a str,
+    span: Span,
+    doc: bool,
+    /// the end of the previous token, if it is on the same line as the comment
+    same_line_as: Option<usize>,
+    prev: Option<usize>,
+    /// the start of the next token
+    next: Option<usize>,
+    printed: bool,
+}
+
+fn comments(source: &str) -> Vec<Comment<'_>> {
+    let mut comments: Vec<Comment<'_>> = Vec::new();
+    let mut prev = None;
+    for token in Lexer::lossless(source) {
+        match token.node {
+            Token::Whitespace(_) => (),
+            Token::Comment(text) | Token::BlockComment(text) | Token::DocComment(text) => {
+                let same_line = prev.filter(|&end| !source[end..token.span.start].contains('\n'));
+                comments.push(Comment {
+                    text: text.trim_end(),
+                    span: token.span,
+                    doc: matches!(token.node, Token::DocComment(_)),
+                    same_line_as: same_line,
+                    prev,
+                    next: None,
+                    printed: false,
+                });
+            }
+            _ => {
+                for comment in comments.iter_mut().rev() {
+                    if comment.next.is_some() {
+                        break;
+                    }
+                    comment.next = Some(token.span.start);
+                }
+                prev = Some(token.span.end);
+            }
+        }
+    }
+    comments
+}
+
+#[derive(Default)]
+struct Printer<'a> {
+    out: String,
+    indent: usize,
+    comments: Vec<Comment<'a>>,
+}
+
+/// How tightly the expression holds together when printed without parentheses
+fn precedence(expr: &Expression) -> Precedence {
+    match expr {
+        Expression::Infix { operator, .. } => {
+            Precedence::of_infix(operator).unwrap_or(Precedence::Lowest)
+        }
+        Expression::Assign { .. } => Precedence::Assign,
+        Expression::Prefix { .. } => Precedence::Prefix,
+        // an `if` is always parenthesized inside of other expressions, which also keeps it from
+        // being read as an `if` statement
+        Expression::If { .. } => Precedence::Lowest,
+        _ => Precedence::Call,
+    }
+}
+
+impl Printer<'_> {
+    /// Prints the statements with the comments before and after each of them
+    fn statements(&mut self, statements: &[Spanned<Statement>]) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+        if let Some(last) = statements.last() {
+            for idx in 0..self.comments.len() {
+                if self.comments[idx].prev == Some(last.span.end) {
+                    self.own_line_comment(idx);
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &Spanned<Statement>) {
+        let has_doc = matches!(&stmt.node, Statement::Let { doc: Some(_), .. });
+        for idx in 0..self.comments.len() {
+            let comment = &mut self.comments[idx];
+            if comment.printed || comment.next != Some(stmt.span.start) {
+                continue;
+            }
+            if comment.doc && has_doc {
+                // printed from the statement
+                comment.printed = true;
+            } else {
+                self.own_line_comment(idx);
+            }
+        }
+
+        self.indent();
+        match &stmt.node {
+            Statement::Let { ident, value, doc } => {
+                for line in doc.iter().flat_map(|doc| doc.split('\n')) {
+                    self.out.push_str("///");
+                    if !line.is_empty() {
+                        self.out.push(' ');
+                        self.out.push_str(line);
+                    }
+                    self.out.push('\n');
+                    self.indent();
+                }
+                self.out.push_str("let ");
+                self.out.push_str(ident);
+                self.out.push_str(" = ");
+                self.expression(value);
+                self.out.push(';');
+            }
+            Statement::Return(value) => {
+                self.out.push_str("return ");
+                self.expression(value);
+                self.out.push(';');
+            }
+            Statement::Expression(expr @ Expression::If { .. }) => self.expression(expr),
+            Statement::Expression(expr) => {
+                self.expression(expr);
+                self.out.push(';');
+            }
+            Statement::Error => self.out.push_str("<error>"),
+        }
+        for comment in &mut self.comments {
+            if !comment.printed && comment.same_line_as == Some(stmt.span.end) {
+                self.out.push(' ');
+                self.out.push_str(comment.text);
+                comment.printed = true;
+            }
+        }
+        self.out.push('\n');
+    }
+
+    fn own_line_comment(&mut self, idx: usize) {
+        if self.comments[idx].printed {
+            return;
+        }
+        self.indent();
+        self.out.push_str(self.comments[idx].text);
+        self.out.push('\n');
+        self.comments[idx].printed = true;
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        if block.statements.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.indent += 1;
+        self.statements(&block.statements);
+        self.indent -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    fn has_comment_before(&self, stmt: &Spanned<Statement>) -> bool {
+        self.comments.iter().any(|comment| comment.next == Some(stmt.span.start))
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Infix { lhs, operator, rhs } => {
+                // operators are left associative, so an operand on the right of the same
+                // precedence needs parentheses
+                let prec = precedence(expr);
+                self.operand(lhs, precedence(lhs) < prec);
+                self.out.push(' ');
+                self.out.push_str(operator.as_str());
+                self.out.push(' ');
+                self.operand(rhs, precedence(rhs) <= prec);
+            }
+            Expression::Assign {
+                ident,
+                operator,
+                value,
+            } => {
+                self.out.push_str(ident);
+                self.out.push(' ');
+                self.out.push_str(operator.as_str());
+                self.out.push(' ');
+                self.operand(value, precedence(value) < Precedence::Assign);
+            }
+            Expression::Prefix { prefix, rhs } => {
+                self.out.push_str(prefix.as_str());
+                self.operand(rhs, precedence(rhs) < Precedence::Prefix);
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.out.push_str("if (");
+                self.expression(condition);
+                self.out.push_str(") ");
+                self.block(consequence);
+                if let Some(alternative) = alternative {
+                    self.out.push_str(" else ");
+                    match alternative.statements.as_slice() {
+                        // a comment between `else` and `if` needs the braces to stay in its place
+                        [stmt @ Spanned {
+                            node: Statement::Expression(expr @ Expression::If { .. }),
+                            ..
+                        }] if !self.has_comment_before(stmt) => self.expression(expr),
+                        _ => self.block(alternative),
+                    }
+                }
+            }
+            Expression::Function { params, body } => {
+                self.out.push_str("fn(");
+                self.out.push_str(&params.join(", "));
+                self.out.push_str(") ");
+                self.block(body);
+            }
+            Expression::Call { function, args, .. } => {
+                self.operand(function, precedence(function) < Precedence::Call);
+                self.out.push('(');
+                self.list(args, |printer, arg| printer.expression(arg));
+                self.out.push(')');
+            }
+            Expression::Array(items) => {
+                self.out.push('[');
+                self.list(items, |printer, item| printer.expression(item));
+                self.out.push(']');
+            }
+            Expression::Index { left, index } => {
+                self.operand(left, precedence(left) < Precedence::Call);
+                self.out.push('[');
+                self.expression(index);
+                self.out.push(']');
+            }
+            Expression::Hash(pairs) => {
+                self.out.push('{');
+                self.list(pairs, |printer, (key, value)| {
+                    printer.expression(key);
+                    printer.out.push_str(": ");
+                    printer.expression(value);
+                });
+                self.out.push('}');
+            }
+            Expression::Identifier(x) => self.out.push_str(x),
+            Expression::NumberLiteral(x) => self.out.push_str(&x.to_string()),
+            Expression::FloatLiteral(x) => self.out.push_str(&format!("{:?}", x)),
+            Expression::BooleanLiteral(x) => self.out.push_str(&x.to_string()),
+            Expression::StringLiteral(x) => {
+                self.out.push('"');
+                self.out.push_str(&lexer::escape(x));
+                self.out.push('"');
+            }
+        }
+    }
+
+    /// Prints the items separated by commas
+    fn list<T>(&mut self, items: &[T], mut item: impl FnMut(&mut Self, &T)) {
+        for (idx, x) in items.iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(", ");
+            }
+            item(self, x);
+        }
+    }
+
+    fn operand(&mut self, expr: &Expression, parens: bool) {
+        if parens {
+            self.out.push('(');
+            self.expression(expr);
+            self.out.push(')');
+        } else {
+            self.expression(expr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn test_pretty(input: &str, expected: &str) {
+        let printed = pretty(&parse(input).unwrap());
+        assert_eq!(printed, expected);
+        assert_eq!(pretty(&parse(&printed).unwrap()), printed);
+    }
+
+    #[test]
+    fn pretty_minimal_parens() {
+        test_pretty("let x = (1 + (2 * 3));", "let x = 1 + 2 * 3;\n");
+        test_pretty("(1 + 2) * 3;", "(1 + 2) * 3;\n");
+        test_pretty("1 - (2 - 3);", "1 - (2 - 3);\n");
+        test_pretty("(1 - 2) - 3;", "1 - 2 - 3;\n");
+        test_pretty("-(a + b);", "-(a + b);\n");
+        test_pretty("!(-a);", "!-a;\n");
+        test_pretty("(f)(x, y + 1);", "f(x, y + 1);\n");
+        test_pretty("(-f)(x);", "(-f)(x);\n");
+        test_pretty("1.5e300 + 0.1 + 1_0;", "1.5e300 + 0.1 + 10;\n");
+        test_pretty("x = (y += 1 % 2);", "x = y += 1 % 2;\n");
+        test_pretty("(x = 1) + 1;", "(x = 1) + 1;\n");
+        test_pretty("(a || b) && c || (d && e);", "(a || b) && c || d && e;\n");
+        test_pretty("(a[0])[1 + 2];", "a[0][1 + 2];\n");
+        test_pretty("(-a)[0] + -a[0];", "(-a)[0] + -a[0];\n");
+        test_pretty("[1,(2+3)] ;", "[1, 2 + 3];\n");
+        test_pretty("{ \"a\" : [], 1: {} };", "{\"a\": [], 1: {}};\n");
+    }
+
+    #[test]
+    fn pretty_blocks() {
+        test_pretty(
+            "let f = fn(x) { if (x > 1) { return x; } else { f(x - 1) } }; f(3);",
+            "let f = fn(x) {\n    if (x > 1) {\n        return x;\n    } else {\n        f(x - 1);\n    }\n};\nf(3);\n",
+        );
+        test_pretty("if (x) {}", "if (x) {}\n");
+        test_pretty(
+            "fn() { ///  a\n///\n/// b\nlet x = 1; };",
+            "fn() {\n    ///  a\n    ///\n    /// b\n    let x = 1;\n};\n",
+        );
+        test_pretty(
+            "1 + if (x) { 1 } else { 2 };",
+            "1 + (if (x) {\n    1;\n} else {\n    2;\n});\n",
+        );
+        test_pretty(
+            "if (a) { 1 } else if (b) { 2 } else { if (c) { 3 } }",
+            "if (a) {\n    1;\n} else if (b) {\n    2;\n} else if (c) {\n    3;\n}\n",
+        );
+    }
+
+    fn test_comments(input: &str, expected: &str) {
+        let printed = pretty_with_comments(&parse(input).unwrap(), input).unwrap();
+        assert_eq!(printed, expected);
+        assert_eq!(pretty_with_comments(&parse(&printed).unwrap(), &printed).unwrap(), printed);
+    }
+
+    #[test]
+    fn pretty_keeps_comments() {
+        test_comments(
+            "// important note\nlet x = 1; /* keep */\n/// doc on expr\nx;",
+            "// important note\nlet x = 1; /* keep */\n/// doc on expr\nx;\n",
+        );
+        test_comments(
+            "/// f\nlet f = fn() {\n// first\nlet a = 1;   // a\n  // last\n};\n// end\n",
+            "/// f\nlet f = fn() {\n    // first\n    let a = 1; // a\n    // last\n};\n// end\n",
+        );
+        test_comments("// only a comment", "// only a comment\n");
+        test_comments(
+            "if (a) {} else // b\nif (b) {}",
+            "if (a) {} else {\n    // b\n    if (b) {}\n}\n",
+        );
+    }
+
+    #[test]
+    fn pretty_refuses_to_move_comments() {
+        let input = "let x = 1 + /* two */ 2;";
+        let err = pretty_with_comments(&parse(input).unwrap(), input).unwrap_err();
+        assert_eq!(&input[err.span.as_range()], "/* two */");
+
+        let input = "if (x) { // empty\n}";
+        assert!(pretty_with_comments(&parse(input).unwrap(), input).is_err());
+    }
+}
@@ -1,24 +1,29 @@
 use std::fmt;
 
 use crate::{
-    common::Peekable,
+    common::{Peekable, Spanned},
     parser::{Parse, ParseResult, Parser},
 };
 
 use super::stmt::Statement;
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Program<'a> {
-    statements: Vec<Statement<'a>>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Program {
+    statements: Vec<Spanned<Statement>>,
 }
 
-impl<'a> Program<'a> {
-    pub fn push(&mut self, statement: Statement<'a>) {
+impl Program {
+    pub fn push(&mut self, statement: Spanned<Statement>) {
         self.statements.push(statement)
     }
+
+    pub fn statements(&self) -> &[Spanned<Statement>] {
+        &self.statements
+    }
 }
 
-impl<'a> fmt::Display for Program<'a> {
+impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -32,7 +37,7 @@ impl<'a> fmt::Display for Program<'a> {
     }
 }
 
-impl Parse for Program<'_> {
+impl Parse for Program {
     fn parse(p: &mut Parser) -> ParseResult<Self> {
         let mut program = Program::default();
 
@@ -40,7 +45,7 @@ impl Parse for Program<'_> {
             if p.lexer().peek().is_none() {
                 return Ok(program);
             }
-            program.push(p.parse()?);
+            program.push(Statement::parse_or_recover(p));
         }
     }
 }
@@ -1,42 +1,88 @@
 use std::fmt;
 
+use crate::common::Spanned;
 use crate::lexer::Token;
 use crate::parser::{Parse, Parser, ParseResult};
 
 use super::Expression;
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Statement<'a> {
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", content = "data")
+)]
+pub enum Statement {
     Let {
-        ident: Token<'a>,
-        value: Expression<'a>,
+        ident: String,
+        value: Expression,
+        /// the `///` comments right before the binding, without the slashes
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        doc: Option<String>,
     },
-    Return(Expression<'a>),
-    Expression(Expression<'a>),
+    Return(Expression),
+    Expression(Expression),
+    /// Placeholder for a statement that could not be parsed
+    Error,
 }
 
-impl<'a> fmt::Display for Statement<'a> {
+impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Statement::Let { ident, value } => write!(f, "let {} = value {};", ident, value),
+            Statement::Let { ident, value, .. } => write!(f, "let {} = {};", ident, value),
             Statement::Return(x) => write!(f, "return {};", x),
+            Statement::Expression(x @ Expression::If { .. }) => write!(f, "{}", x),
             Statement::Expression(x) => write!(f, "{};", x),
+            Statement::Error => write!(f, "<error>"),
         }
     }
 }
 
-impl Parse for Statement<'_> {
+impl Statement {
+    /// Parses a statement together with its span. If that fails the error is recorded, the parser
+    /// skips to the next statement and `Statement::Error` is returned.
+    pub fn parse_or_recover(p: &mut Parser) -> Spanned<Statement> {
+        let start = p.lexer.peek_span();
+        let stmt = p.parse().unwrap_or_else(|err| {
+            p.record(err);
+            p.synchronize(start);
+            Statement::Error
+        });
+        Spanned::new(stmt, start.to(p.lexer.curr_span()))
+    }
+
+    fn parse_let(p: &mut Parser) -> ParseResult<Self> {
+        let doc = p.lexer.take_docs();
+        p.next_or_err()?;
+        let ident = p.expect_ident()?.to_string();
+        p.expect(Token::Assign)?;
+        Ok(Statement::Let {
+            ident,
+            value: p.parse()?,
+            doc,
+        })
+    }
+
+    fn parse_return(p: &mut Parser) -> ParseResult<Self> {
+        p.next_or_err()?;
+        Ok(Statement::Return(p.parse()?))
+    }
+}
+
+impl Parse for Statement {
     fn parse(p: &mut Parser) -> ParseResult<Self> {
-        let res = match p.peek_or_err()? {
-            Token::Let => {
-                panic!()
-            }
-            Token::Return => {
-                panic!()
-            }
-            _ => Statement::Expression(p.parse()?),
-        };
-        p.expect(Token::Semicolon)?;
+        let res = match p.peek_or_err()?.node {
+            Token::Let => Statement::parse_let(p),
+            Token::Return => Statement::parse_return(p),
+            _ => p.parse().map(Statement::Expression),
+        }?;
+
+        // the semicolon can be left out for the last statement of a block and after if expressions
+        let semicolon_optional = matches!(res, Statement::Expression(Expression::If { .. }))
+            || p.peek_is(Token::Rbrace);
+        if p.peek_is(Token::Semicolon) || !semicolon_optional {
+            p.expect(Token::Semicolon)?;
+        }
         Ok(res)
     }
 }
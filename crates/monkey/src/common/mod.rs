@@ -1,8 +1,14 @@
+mod source_map;
+mod span;
+
 use std::fmt;
 use std::iter::FusedIterator;
 
 use log::info;
 
+pub use source_map::{FileId, LineCol, LineIndex, Location, SourceFile, SourceMap};
+pub use span::{Span, Spanned};
+
 /// Advanced iter is and iterator that is advanced one. It is like Peekable<T> except the peek item
 /// is already advanced.
 #[derive(Debug, Clone)]
@@ -119,7 +125,7 @@ pub trait Accept<T: PartialEq + fmt::Debug>: Iterator<Item = T> + Peekable {
     /// false for.
     fn accept_while(&mut self, predicate: impl Fn(&Self::Item) -> bool) {
         while let Some(c) = self.peek() {
-            if !predicate(&c) {
+            if !predicate(c) {
                 info!("char `{:?}` is not accepted", c);
                 break;
             } else {
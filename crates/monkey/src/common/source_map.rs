@@ -0,0 +1,389 @@
+use std::fmt;
+
+use super::Span;
+
+/// A 1-based line and column pair. Whether the column counts chars, UTF-8 bytes or UTF-16 code
+/// units depends on which method of `LineIndex` produced it.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl LineCol {
+    pub fn new(line: usize, col: usize) -> LineCol {
+        LineCol { line, col }
+    }
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// A char that takes up more than one byte in UTF-8, stored as byte offsets relative to the start
+/// of its line.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+struct WideChar {
+    start: usize,
+    end: usize,
+}
+
+impl WideChar {
+    fn len_utf8(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn len_utf16(&self) -> usize {
+        if self.len_utf8() == 4 {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+/// Converts between byte offsets and line/column positions of a single source string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// byte offset of the start of every line, the first line always starts at 0
+    line_starts: Vec<usize>,
+    /// multi byte chars of every line, used to convert UTF-8 columns to other columns
+    wide_chars: Vec<Vec<WideChar>>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(input: &str) -> LineIndex {
+        let mut line_starts = vec![0];
+        let mut wide_chars = vec![Vec::new()];
+
+        for (idx, c) in input.char_indices() {
+            if c == '\n' {
+                line_starts.push(idx + 1);
+                wide_chars.push(Vec::new());
+            } else if c.len_utf8() > 1 {
+                let line_start = *line_starts.last().unwrap();
+                let start = idx - line_start;
+                wide_chars
+                    .last_mut()
+                    .unwrap()
+                    .push(WideChar { start, end: start + c.len_utf8() });
+            }
+        }
+
+        LineIndex {
+            line_starts,
+            wide_chars,
+            len: input.len(),
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Converts a byte offset into a line and a column that counts the chars before it, which is
+    /// the column people expect. An offset inside of a char is the column of that char, offsets
+    /// past the end of the input are clamped to the end.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let LineCol { line, col } = self.line_byte_col(offset);
+        let byte_col = col - 1;
+        let mut col = byte_col;
+        for c in self.line_wide_chars(line) {
+            if c.start >= byte_col {
+                break;
+            }
+            if c.end > byte_col {
+                col -= byte_col - c.start;
+                break;
+            }
+            col -= c.len_utf8() - 1;
+        }
+        LineCol::new(line, col + 1)
+    }
+
+    /// Converts a byte offset into a line and UTF-8 column. Offsets past the end of the input are
+    /// clamped to the end.
+    pub fn line_byte_col(&self, offset: usize) -> LineCol {
+        let offset = offset.min(self.len);
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        LineCol::new(line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    /// Converts a byte offset into a line and UTF-16 column, the format used by most editors
+    pub fn line_col_utf16(&self, offset: usize) -> LineCol {
+        let line_col = self.line_byte_col(offset);
+        self.to_utf16(line_col).expect("line_byte_col returns 1-based columns")
+    }
+
+    /// Converts a line and char column back into a byte offset. Returns `None` if the position is
+    /// outside of the input.
+    pub fn offset(&self, line_col: LineCol) -> Option<usize> {
+        let mut col = line_col.col.checked_sub(1)?;
+        for c in self.line_wide_chars(line_col.line) {
+            if c.start >= col {
+                break;
+            }
+            col += c.len_utf8() - 1;
+        }
+        self.offset_byte_col(LineCol::new(line_col.line, col + 1))
+    }
+
+    /// Converts a line and UTF-8 column back into a byte offset. Returns `None` if the position is
+    /// outside of the input.
+    pub fn offset_byte_col(&self, line_col: LineCol) -> Option<usize> {
+        let line = line_col.line.checked_sub(1)?;
+        let col = line_col.col.checked_sub(1)?;
+        let offset = self.line_starts.get(line)? + col;
+        let line_end = self.line_starts.get(line + 1).copied().unwrap_or(self.len + 1);
+        if offset < line_end {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    /// Converts a line and UTF-16 column back into a byte offset
+    pub fn offset_utf16(&self, line_col: LineCol) -> Option<usize> {
+        self.offset_byte_col(self.to_utf8(line_col)?)
+    }
+
+    /// Converts a line and UTF-8 column into a line and UTF-16 column. Returns `None` for column 0.
+    pub fn to_utf16(&self, line_col: LineCol) -> Option<LineCol> {
+        let utf8_col = line_col.col.checked_sub(1)?;
+        let mut col = utf8_col;
+        for c in self.line_wide_chars(line_col.line) {
+            if c.end > utf8_col {
+                break;
+            }
+            col -= c.len_utf8() - c.len_utf16();
+        }
+        Some(LineCol::new(line_col.line, col + 1))
+    }
+
+    /// Converts a line and UTF-16 column into a line and UTF-8 column. Returns `None` for column 0.
+    pub fn to_utf8(&self, line_col: LineCol) -> Option<LineCol> {
+        let mut col = line_col.col.checked_sub(1)?;
+        for c in self.line_wide_chars(line_col.line) {
+            if c.start >= col {
+                break;
+            }
+            col += c.len_utf8() - c.len_utf16();
+        }
+        Some(LineCol::new(line_col.line, col + 1))
+    }
+
+    /// The span of a 1-based line, not including the line break
+    pub fn line_span(&self, line: usize) -> Option<Span> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = match self.line_starts.get(line) {
+            Some(next_start) => next_start - 1,
+            None => self.len,
+        };
+        Some(Span::new(start, end))
+    }
+
+    fn line_wide_chars(&self, line: usize) -> &[WideChar] {
+        line.checked_sub(1)
+            .and_then(|line| self.wide_chars.get(line))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Identifies a file that was added to a `SourceMap`
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, PartialOrd, Ord)]
+pub struct FileId(usize);
+
+/// A named source file together with its line index
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    name: String,
+    source: String,
+    line_index: LineIndex,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> SourceFile {
+        let name = name.into();
+        let source = source.into();
+        let line_index = LineIndex::new(&source);
+        SourceFile {
+            name,
+            source,
+            line_index,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.line_index
+    }
+
+    /// The text of a 1-based line, not including the line break
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        let span = self.line_index.line_span(line)?;
+        self.source
+            .get(span.as_range())
+            .map(|line| line.trim_end_matches('\r'))
+    }
+
+    pub fn location(&self, offset: usize) -> Location<'_> {
+        Location {
+            file: &self.name,
+            line_col: self.line_index.line_col(offset),
+        }
+    }
+}
+
+/// A human readable position in a named file, displayed as `file:line:col` where the column
+/// counts chars
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line_col: LineCol,
+}
+
+impl<'a> fmt::Display for Location<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line_col)
+    }
+}
+
+/// Holds every source file that is part of a run so that offsets from any of them can be turned
+/// into locations.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile::new(name, source));
+        id
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(idx, file)| (FileId(idx), file))
+    }
+
+    pub fn location(&self, id: FileId, offset: usize) -> Location<'_> {
+        self.get(id).location(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col_simple() {
+        let index = LineIndex::new("let x = 5;\nlet y = 10;\n");
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line_col(0), LineCol::new(1, 1));
+        assert_eq!(index.line_col(4), LineCol::new(1, 5));
+        assert_eq!(index.line_col(10), LineCol::new(1, 11));
+        assert_eq!(index.line_col(11), LineCol::new(2, 1));
+        assert_eq!(index.line_col(15), LineCol::new(2, 5));
+        assert_eq!(index.line_col(23), LineCol::new(3, 1));
+        assert_eq!(index.line_col(100), LineCol::new(3, 1));
+    }
+
+    #[test]
+    fn offset_round_trip() {
+        let input = "let x = 5;\nlet y = 10;\n";
+        let index = LineIndex::new(input);
+        for offset in 0..=input.len() {
+            assert_eq!(index.offset(index.line_col(offset)), Some(offset));
+        }
+        assert_eq!(index.offset(LineCol::new(1, 12)), None);
+        assert_eq!(index.offset(LineCol::new(4, 1)), None);
+        assert_eq!(index.offset(LineCol::new(0, 1)), None);
+    }
+
+    #[test]
+    fn unicode_columns() {
+        let input = "let Здравствуйте = 100;\nЗ = é;";
+        let index = LineIndex::new(input);
+        let eq = input.find('=').unwrap();
+        assert_eq!(index.line_col(eq), LineCol::new(1, 18));
+        assert_eq!(index.offset(LineCol::new(1, 18)), Some(eq));
+        assert_eq!(index.line_byte_col(eq), LineCol::new(1, 30));
+        assert_eq!(index.offset_byte_col(LineCol::new(1, 30)), Some(eq));
+        assert_eq!(index.line_col_utf16(eq), LineCol::new(1, 18));
+        assert_eq!(index.offset_utf16(LineCol::new(1, 18)), Some(eq));
+
+        for (offset, _) in input.char_indices() {
+            assert_eq!(index.offset(index.line_col(offset)), Some(offset));
+        }
+        // inside of a char
+        assert_eq!(index.line_col(5), LineCol::new(1, 5));
+        assert_eq!(index.line_col(input.rfind('é').unwrap() + 1), LineCol::new(2, 5));
+    }
+
+    #[test]
+    fn surrogate_pair_columns() {
+        let input = "x 🐒 y";
+        let index = LineIndex::new(input);
+        let y = input.find('y').unwrap();
+        assert_eq!(index.line_col(y), LineCol::new(1, 5));
+        assert_eq!(index.line_byte_col(y), LineCol::new(1, 8));
+        assert_eq!(index.line_col_utf16(y), LineCol::new(1, 6));
+        assert_eq!(index.offset_utf16(LineCol::new(1, 6)), Some(y));
+    }
+
+    #[test]
+    fn column_zero() {
+        let index = LineIndex::new("x 🐒 y");
+        assert_eq!(index.offset(LineCol::new(1, 0)), None);
+        assert_eq!(index.offset_byte_col(LineCol::new(1, 0)), None);
+        assert_eq!(index.offset_utf16(LineCol::new(1, 0)), None);
+        assert_eq!(index.to_utf16(LineCol::new(1, 0)), None);
+        assert_eq!(index.to_utf8(LineCol::new(1, 0)), None);
+    }
+
+    #[test]
+    fn line_text() {
+        let file = SourceFile::new("main.mk", "first\r\nsecond\nthird");
+        assert_eq!(file.line_text(1), Some("first"));
+        assert_eq!(file.line_text(2), Some("second"));
+        assert_eq!(file.line_text(3), Some("third"));
+        assert_eq!(file.line_text(4), None);
+    }
+
+    #[test]
+    fn source_map_locations() {
+        let mut map = SourceMap::new();
+        let main = map.add("main.mk", "let x = lib;\n");
+        let lib = map.add("lib.mk", "\n\nlet y = 1;");
+        assert_eq!(map.location(main, 8).to_string(), "main.mk:1:9");
+        assert_eq!(map.location(lib, 6).to_string(), "lib.mk:3:5");
+        let unicode = map.add("unicode.mk", "let é = x;");
+        assert_eq!(map.location(unicode, 9).to_string(), "unicode.mk:1:9");
+        assert_eq!(map.files().count(), 3);
+    }
+}
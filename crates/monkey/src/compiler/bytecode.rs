@@ -0,0 +1,63 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::common::Span;
+
+/// The output of the compiler, run by `vm::Vm`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    /// the top level statements, as a function without parameters
+    pub main: Rc<CompiledFunction>,
+    pub constants: Vec<Constant>,
+    /// the name of each global by index, for error messages and builtins
+    pub globals: Vec<String>,
+}
+
+/// A literal or function stored in the constants pool
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Function(Rc<CompiledFunction>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    /// the name it was bound to with `let`, if any
+    pub name: Option<String>,
+    pub num_params: usize,
+    /// the number of local slots, including the parameters
+    pub num_locals: usize,
+    pub instructions: Vec<u8>,
+    /// the statements the instructions came from, for runtime errors
+    pub spans: Vec<SpanEntry>,
+}
+
+/// The instructions `start..end` were compiled from the statement at `span`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanEntry {
+    pub start: usize,
+    pub end: usize,
+    pub span: Span,
+}
+
+impl CompiledFunction {
+    /// The span of the innermost statement containing the instruction at the offset
+    pub fn span_at(&self, offset: usize) -> Option<Span> {
+        self.spans
+            .iter()
+            .filter(|entry| (entry.start..entry.end).contains(&offset))
+            .min_by_key(|entry| entry.end - entry.start)
+            .map(|entry| entry.span)
+    }
+}
+
+impl fmt::Display for CompiledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "compiled function {}/{}", name, self.num_params),
+            None => write!(f, "compiled function/{}", self.num_params),
+        }
+    }
+}
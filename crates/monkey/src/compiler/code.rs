@@ -0,0 +1,268 @@
+use std::fmt;
+
+use crate::lexer::Token;
+
+/// The first byte of every instruction. Operands follow the opcode, see `Opcode::operand_widths`.
+/// The discriminants are part of the bytecode, so new opcodes go at the end.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    /// pushes the constant with the index
+    Constant,
+    Null,
+    True,
+    False,
+    Pop,
+    /// pushes the top of the stack again
+    Dup,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Minus,
+    Bang,
+    /// continues at the offset
+    Jump,
+    /// pops the condition and continues at the offset if it is falsy
+    JumpIfFalse,
+    GetGlobal,
+    /// pops the value and binds the global, like `let`
+    SetGlobal,
+    /// pops the value into a global that must be bound already, like `=`
+    AssignGlobal,
+    /// pushes a local that is always bound, like a parameter
+    GetLocal,
+    /// pops the value into the local, binding it
+    SetLocal,
+    GetFree,
+    /// pops the value into a free variable that is always bound
+    SetFree,
+    /// pops the number of elements and pushes an array
+    Array,
+    /// pops the number of key value pairs and pushes a hash
+    Hash,
+    Index,
+    /// calls the function below the number of arguments
+    Call,
+    ReturnValue,
+    /// creates a closure of the function constant. The number of captured variables is followed
+    /// by a pair of bytes for each one: 1 and a local index, or 0 and a free variable index.
+    Closure,
+    /// pushes the local and continues at the offset if it is bound, otherwise does nothing. Names
+    /// are looked up with these before falling back to the next binding.
+    TryGetLocal,
+    TryGetFree,
+    /// pops the value into the local and continues at the offset if it is bound, otherwise does
+    /// nothing
+    TryAssignLocal,
+    TryAssignFree,
+}
+
+const OPCODES: &[Opcode] = &[
+    Opcode::Constant,
+    Opcode::Null,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Pop,
+    Opcode::Dup,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::Less,
+    Opcode::Greater,
+    Opcode::LessEqual,
+    Opcode::GreaterEqual,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::Jump,
+    Opcode::JumpIfFalse,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::AssignGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetFree,
+    Opcode::SetFree,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Closure,
+    Opcode::TryGetLocal,
+    Opcode::TryGetFree,
+    Opcode::TryAssignLocal,
+    Opcode::TryAssignFree,
+];
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(usize::from(byte)).copied()
+    }
+
+    /// The size in bytes of each operand, they are stored big endian
+    pub fn operand_widths(self) -> &'static [usize] {
+        match self {
+            Opcode::Constant
+            | Opcode::Jump
+            | Opcode::JumpIfFalse
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal
+            | Opcode::AssignGlobal
+            | Opcode::Array
+            | Opcode::Hash => &[2],
+            Opcode::GetLocal
+            | Opcode::SetLocal
+            | Opcode::GetFree
+            | Opcode::SetFree
+            | Opcode::Call => &[1],
+            Opcode::Closure => &[2, 1],
+            Opcode::TryGetLocal
+            | Opcode::TryGetFree
+            | Opcode::TryAssignLocal
+            | Opcode::TryAssignFree => &[1, 2],
+            _ => &[],
+        }
+    }
+
+    /// The opcode of a binary operator
+    pub fn infix(operator: Token<'_>) -> Option<Opcode> {
+        Some(match operator {
+            Token::Plus => Opcode::Add,
+            Token::Minus => Opcode::Sub,
+            Token::Asterisk => Opcode::Mul,
+            Token::Slash => Opcode::Div,
+            Token::Percent => Opcode::Mod,
+            Token::Eq => Opcode::Equal,
+            Token::NotEq => Opcode::NotEqual,
+            Token::Lt => Opcode::Less,
+            Token::Gt => Opcode::Greater,
+            Token::LtEq => Opcode::LessEqual,
+            Token::GtEq => Opcode::GreaterEqual,
+            _ => return None,
+        })
+    }
+
+    /// The operator token of an opcode from `infix` or `prefix`, the evaluator implements them
+    pub fn operator(self) -> Option<Token<'static>> {
+        Some(match self {
+            Opcode::Add => Token::Plus,
+            Opcode::Sub | Opcode::Minus => Token::Minus,
+            Opcode::Mul => Token::Asterisk,
+            Opcode::Div => Token::Slash,
+            Opcode::Mod => Token::Percent,
+            Opcode::Equal => Token::Eq,
+            Opcode::NotEqual => Token::NotEq,
+            Opcode::Less => Token::Lt,
+            Opcode::Greater => Token::Gt,
+            Opcode::LessEqual => Token::LtEq,
+            Opcode::GreaterEqual => Token::GtEq,
+            Opcode::Bang => Token::Bang,
+            _ => return None,
+        })
+    }
+
+    /// The opcode of a prefix operator
+    pub fn prefix(operator: Token<'_>) -> Option<Opcode> {
+        match operator {
+            Token::Minus => Some(Opcode::Minus),
+            Token::Bang => Some(Opcode::Bang),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("{:?}", self))
+    }
+}
+
+/// Encodes an instruction. The operands must fit their widths, the extra bytes of `Closure` are
+/// appended by the caller.
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+    let widths = op.operand_widths();
+    debug_assert_eq!(widths.len(), operands.len(), "operands of {}", op);
+    let mut instruction = vec![op as u8];
+    for (&operand, &width) in operands.iter().zip(widths) {
+        debug_assert!(operand >> (8 * width) == 0, "operand {} of {}", operand, op);
+        for byte in (0..width).rev() {
+            instruction.push((operand >> (8 * byte)) as u8);
+        }
+    }
+    instruction
+}
+
+/// A decoded instruction, see `read_instruction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub op: Opcode,
+    /// unused operands are zero
+    pub operands: [usize; 2],
+    /// the offset of the next instruction
+    pub next: usize,
+}
+
+/// Decodes the instruction at the offset, or returns `None` if the opcode is unknown or the
+/// instruction is cut off
+pub fn read_instruction(code: &[u8], offset: usize) -> Option<Instruction> {
+    let op = Opcode::from_byte(*code.get(offset)?)?;
+    let mut operands = [0; 2];
+    let mut next = offset + 1;
+    for (operand, &width) in operands.iter_mut().zip(op.operand_widths()) {
+        let bytes = code.get(next..next + width)?;
+        *operand = bytes.iter().fold(0, |acc, &byte| acc << 8 | usize::from(byte));
+        next += width;
+    }
+    if op == Opcode::Closure {
+        next += 2 * operands[1];
+        if next > code.len() {
+            return None;
+        }
+    }
+    Some(Instruction { op, operands, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcodes_match_their_bytes() {
+        for (byte, &op) in OPCODES.iter().enumerate() {
+            assert_eq!(op as usize, byte);
+            assert_eq!(Opcode::from_byte(byte as u8), Some(op));
+        }
+        assert_eq!(Opcode::from_byte(OPCODES.len() as u8), None);
+    }
+
+    #[test]
+    fn make_and_read() {
+        let code = make(Opcode::Constant, &[65534]);
+        assert_eq!(code, [Opcode::Constant as u8, 255, 254]);
+        let instruction = read_instruction(&code, 0).unwrap();
+        assert_eq!(instruction.operands, [65534, 0]);
+        assert_eq!(instruction.next, 3);
+
+        let mut code = make(Opcode::Closure, &[1, 2]);
+        assert_eq!(read_instruction(&code, 0), None);
+        code.extend(&[1, 0, 0, 0]);
+        let instruction = read_instruction(&code, 0).unwrap();
+        assert_eq!(instruction.operands, [1, 2]);
+        assert_eq!(instruction.next, 8);
+
+        assert_eq!(read_instruction(&[Opcode::GetLocal as u8], 0), None);
+        assert_eq!(read_instruction(&[255], 0), None);
+    }
+}
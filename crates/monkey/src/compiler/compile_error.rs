@@ -0,0 +1,32 @@
+use thiserror::Error;
+
+/// Programs that parsed but do not fit the limits of the bytecode
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+    #[error("Cannot compile a statement that failed to parse")]
+    ParseError,
+
+    #[error("Operator `{0}` cannot be compiled")]
+    UnsupportedOperator(String),
+
+    #[error("Too many constants, at most 65536 are allowed")]
+    TooManyConstants,
+
+    #[error("Too many global variables, at most 65536 are allowed")]
+    TooManyGlobals,
+
+    #[error("Too many local variables in a function, at most 256 are allowed")]
+    TooManyLocals,
+
+    #[error("Too many variables captured by a function, at most 255 are allowed")]
+    TooManyFreeVariables,
+
+    #[error("Too many arguments in a call, at most 255 are allowed")]
+    TooManyArguments,
+
+    #[error("Too many elements in a literal, at most 65535 are allowed")]
+    TooManyElements,
+
+    #[error("Function is too large, jumps can only reach 65535 bytes")]
+    FunctionTooLarge,
+}
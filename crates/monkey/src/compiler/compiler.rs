@@ -0,0 +1,622 @@
+use std::rc::Rc;
+
+use log::debug;
+
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+use crate::common::Spanned;
+use crate::lexer::Token;
+
+use super::code::{make, Opcode};
+use super::symbol_table::{Scope, Symbol, SymbolTable};
+use super::{Bytecode, CompileError, CompiledFunction, Constant, SpanEntry};
+
+const MAX_CONSTANTS: usize = 1 << 16;
+const MAX_OPERAND: usize = u16::MAX as usize;
+
+/// Compiles a program to bytecode for `vm::Vm`
+pub fn compile(program: &Program) -> Result<Bytecode, CompileError> {
+    Compiler::new().compile_program(program)
+}
+
+/// The instructions of the function that is being compiled
+#[derive(Debug, Default)]
+struct FunctionScope {
+    instructions: Vec<u8>,
+    spans: Vec<SpanEntry>,
+}
+
+/// Lowers the AST to bytecode. Every expression leaves exactly one value on the stack, blocks
+/// leave the value of their last statement like in the evaluator.
+#[derive(Debug)]
+struct Compiler {
+    constants: Vec<Constant>,
+    symbols: SymbolTable,
+    scopes: Vec<FunctionScope>,
+}
+
+impl Compiler {
+    fn new() -> Compiler {
+        Compiler {
+            constants: Vec::new(),
+            symbols: SymbolTable::new(),
+            scopes: vec![FunctionScope::default()],
+        }
+    }
+
+    fn compile_program(mut self, program: &Program) -> Result<Bytecode, CompileError> {
+        self.block(program.statements())?;
+        self.emit(Opcode::ReturnValue, &[]);
+        let main = self.finish_function(None, 0, 0);
+        Ok(Bytecode {
+            main: Rc::new(main),
+            constants: self.constants,
+            globals: self.symbols.global_names().to_vec(),
+        })
+    }
+
+    fn scope(&mut self) -> &mut FunctionScope {
+        self.scopes.last_mut().expect("there is always a function scope")
+    }
+
+    fn offset(&mut self) -> usize {
+        self.scope().instructions.len()
+    }
+
+    /// Appends the instruction and returns its offset
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+        let offset = self.offset();
+        self.scope().instructions.extend(make(op, operands));
+        offset
+    }
+
+    /// Points the jump at the offset to the next instruction. The target is the last operand.
+    fn patch_jump(&mut self, jump: usize) -> Result<(), CompileError> {
+        let target = self.offset();
+        if target > MAX_OPERAND {
+            return Err(CompileError::FunctionTooLarge);
+        }
+        let instructions = &mut self.scope().instructions;
+        let op = Opcode::from_byte(instructions[jump]).expect("a jump was emitted");
+        let end = jump + 1 + op.operand_widths().iter().sum::<usize>();
+        instructions[end - 2] = (target >> 8) as u8;
+        instructions[end - 1] = target as u8;
+        Ok(())
+    }
+
+    fn constant(&mut self, constant: Constant) -> Result<(), CompileError> {
+        if self.constants.len() == MAX_CONSTANTS {
+            return Err(CompileError::TooManyConstants);
+        }
+        self.constants.push(constant);
+        self.emit(Opcode::Constant, &[self.constants.len() - 1]);
+        Ok(())
+    }
+
+    fn finish_function(
+        &mut self,
+        name: Option<&str>,
+        num_params: usize,
+        num_locals: usize,
+    ) -> CompiledFunction {
+        let scope = self.scopes.pop().expect("there is always a function scope");
+        CompiledFunction {
+            name: name.map(str::to_string),
+            num_params,
+            num_locals,
+            instructions: scope.instructions,
+            spans: scope.spans,
+        }
+    }
+
+    /// Compiles the statements so that they leave the value of the last one
+    fn block(&mut self, statements: &[Spanned<Statement>]) -> Result<(), CompileError> {
+        if statements.is_empty() {
+            self.emit(Opcode::Null, &[]);
+        }
+        for (idx, stmt) in statements.iter().enumerate() {
+            let pushed = self.statement(stmt)?;
+            let last = idx + 1 == statements.len();
+            if last && !pushed {
+                self.emit(Opcode::Null, &[]);
+            } else if !last && pushed {
+                self.emit(Opcode::Pop, &[]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the statement left a value on the stack
+    fn statement(&mut self, stmt: &Spanned<Statement>) -> Result<bool, CompileError> {
+        let start = self.offset();
+        let pushed = match &stmt.node {
+            Statement::Let { ident, value, .. } => {
+                match value {
+                    Expression::Function { params, body } => {
+                        self.function(Some(ident), params, body)?
+                    }
+                    value => self.expression(value)?,
+                }
+                let symbol = self.symbols.define(ident)?;
+                match symbol.scope {
+                    Scope::Global => self.emit(Opcode::SetGlobal, &[symbol.index]),
+                    _ => self.emit(Opcode::SetLocal, &[symbol.index]),
+                };
+                false
+            }
+            Statement::Return(value) => {
+                self.expression(value)?;
+                self.emit(Opcode::ReturnValue, &[]);
+                false
+            }
+            Statement::Expression(expr) => {
+                self.expression(expr)?;
+                true
+            }
+            Statement::Error => return Err(CompileError::ParseError),
+        };
+        let end = self.offset();
+        self.scope().spans.push(SpanEntry {
+            start,
+            end,
+            span: stmt.span,
+        });
+        Ok(pushed)
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Result<(), CompileError> {
+        match expr {
+            Expression::NumberLiteral(x) => self.constant(Constant::Integer(*x)),
+            Expression::FloatLiteral(x) => self.constant(Constant::Float(*x)),
+            Expression::StringLiteral(x) => self.constant(Constant::String(x.clone())),
+            Expression::BooleanLiteral(true) => {
+                self.emit(Opcode::True, &[]);
+                Ok(())
+            }
+            Expression::BooleanLiteral(false) => {
+                self.emit(Opcode::False, &[]);
+                Ok(())
+            }
+            Expression::Identifier(name) => {
+                let chain = self.symbols.resolve(name)?;
+                self.load(&chain)
+            }
+            Expression::Prefix { prefix, rhs } => self.prefix(*prefix, rhs),
+            Expression::Infix {
+                lhs,
+                operator: operator @ (Token::And | Token::Or),
+                rhs,
+            } => self.logical(lhs, *operator, rhs),
+            Expression::Infix { lhs, operator, rhs } => {
+                self.expression(lhs)?;
+                self.expression(rhs)?;
+                self.infix(*operator)
+            }
+            Expression::Assign {
+                ident,
+                operator,
+                value,
+            } => self.assignment(ident, *operator, value),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => self.conditional(condition, consequence, alternative.as_ref()),
+            Expression::Function { params, body } => self.function(None, params, body),
+            Expression::Call { function, args, .. } => self.call(function, args),
+            Expression::Array(items) => self.array(items),
+            Expression::Index { left, index } => {
+                self.expression(left)?;
+                self.expression(index)?;
+                self.emit(Opcode::Index, &[]);
+                Ok(())
+            }
+            Expression::Hash(pairs) => self.hash(pairs),
+        }
+    }
+
+    fn prefix(&mut self, prefix: Token<'_>, rhs: &Expression) -> Result<(), CompileError> {
+        self.expression(rhs)?;
+        let op = Opcode::prefix(prefix)
+            .ok_or_else(|| CompileError::UnsupportedOperator(prefix.to_string()))?;
+        self.emit(op, &[]);
+        Ok(())
+    }
+
+    fn assignment(
+        &mut self,
+        ident: &str,
+        operator: Token<'_>,
+        value: &Expression,
+    ) -> Result<(), CompileError> {
+        let chain = self.symbols.resolve(ident)?;
+        match operator.compound_operator() {
+            Some(operator) => {
+                self.load(&chain)?;
+                self.expression(value)?;
+                self.infix(operator)?;
+            }
+            None => self.expression(value)?,
+        }
+        // the assignment is an expression, so its value stays on the stack
+        self.emit(Opcode::Dup, &[]);
+        self.assign(&chain)
+    }
+
+    fn conditional(
+        &mut self,
+        condition: &Expression,
+        consequence: &BlockStatement,
+        alternative: Option<&BlockStatement>,
+    ) -> Result<(), CompileError> {
+        self.expression(condition)?;
+        let jump_if_false = self.emit(Opcode::JumpIfFalse, &[MAX_OPERAND]);
+        self.block(&consequence.statements)?;
+        let jump = self.emit(Opcode::Jump, &[MAX_OPERAND]);
+        self.patch_jump(jump_if_false)?;
+        match alternative {
+            Some(alternative) => self.block(&alternative.statements)?,
+            None => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+        self.patch_jump(jump)
+    }
+
+    fn call(&mut self, function: &Expression, args: &[Expression]) -> Result<(), CompileError> {
+        self.expression(function)?;
+        for arg in args {
+            self.expression(arg)?;
+        }
+        if args.len() > usize::from(u8::MAX) {
+            return Err(CompileError::TooManyArguments);
+        }
+        self.emit(Opcode::Call, &[args.len()]);
+        Ok(())
+    }
+
+    fn array(&mut self, items: &[Expression]) -> Result<(), CompileError> {
+        for item in items {
+            self.expression(item)?;
+        }
+        if items.len() > MAX_OPERAND {
+            return Err(CompileError::TooManyElements);
+        }
+        self.emit(Opcode::Array, &[items.len()]);
+        Ok(())
+    }
+
+    fn hash(&mut self, pairs: &[(Expression, Expression)]) -> Result<(), CompileError> {
+        for (key, value) in pairs {
+            self.expression(key)?;
+            self.expression(value)?;
+        }
+        if pairs.len() > MAX_OPERAND {
+            return Err(CompileError::TooManyElements);
+        }
+        self.emit(Opcode::Hash, &[pairs.len()]);
+        Ok(())
+    }
+
+    /// Pushes the first bound symbol of the chain from `SymbolTable::resolve`
+    fn load(&mut self, chain: &[Symbol]) -> Result<(), CompileError> {
+        let (last, fallbacks) = chain.split_last().expect("a name resolves to a symbol");
+        let mut jumps = Vec::new();
+        for symbol in fallbacks {
+            let op = match symbol.scope {
+                Scope::Local => Opcode::TryGetLocal,
+                _ => Opcode::TryGetFree,
+            };
+            jumps.push(self.emit(op, &[symbol.index, MAX_OPERAND]));
+        }
+        let op = match last.scope {
+            Scope::Global => Opcode::GetGlobal,
+            Scope::Local => Opcode::GetLocal,
+            Scope::Free => Opcode::GetFree,
+        };
+        self.emit(op, &[last.index]);
+        for jump in jumps {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    /// Pops the value into the first bound symbol of the chain, like `load`
+    fn assign(&mut self, chain: &[Symbol]) -> Result<(), CompileError> {
+        let (last, fallbacks) = chain.split_last().expect("a name resolves to a symbol");
+        let mut jumps = Vec::new();
+        for symbol in fallbacks {
+            let op = match symbol.scope {
+                Scope::Local => Opcode::TryAssignLocal,
+                _ => Opcode::TryAssignFree,
+            };
+            jumps.push(self.emit(op, &[symbol.index, MAX_OPERAND]));
+        }
+        let op = match last.scope {
+            Scope::Global => Opcode::AssignGlobal,
+            Scope::Local => Opcode::SetLocal,
+            Scope::Free => Opcode::SetFree,
+        };
+        self.emit(op, &[last.index]);
+        for jump in jumps {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    fn infix(&mut self, operator: Token<'_>) -> Result<(), CompileError> {
+        let op = Opcode::infix(operator)
+            .ok_or_else(|| CompileError::UnsupportedOperator(operator.to_string()))?;
+        self.emit(op, &[]);
+        Ok(())
+    }
+
+    /// `&&` and `||` only evaluate the right hand side if it decides the result, which is always
+    /// a boolean
+    fn logical(
+        &mut self,
+        lhs: &Expression,
+        operator: Token<'_>,
+        rhs: &Expression,
+    ) -> Result<(), CompileError> {
+        self.expression(lhs)?;
+        let jump_if_false = self.emit(Opcode::JumpIfFalse, &[MAX_OPERAND]);
+        if operator == Token::Or {
+            self.emit(Opcode::True, &[]);
+            let jump = self.emit(Opcode::Jump, &[MAX_OPERAND]);
+            self.patch_jump(jump_if_false)?;
+            self.expression(rhs)?;
+            self.truthy();
+            self.patch_jump(jump)?;
+        } else {
+            self.expression(rhs)?;
+            self.truthy();
+            let jump = self.emit(Opcode::Jump, &[MAX_OPERAND]);
+            self.patch_jump(jump_if_false)?;
+            self.emit(Opcode::False, &[]);
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the value with whether it is truthy
+    fn truthy(&mut self) {
+        self.emit(Opcode::Bang, &[]);
+        self.emit(Opcode::Bang, &[]);
+    }
+
+    /// Compiles the function into a constant and emits the `Closure` instruction that captures
+    /// its free variables
+    fn function(
+        &mut self,
+        name: Option<&str>,
+        params: &[String],
+        body: &BlockStatement,
+    ) -> Result<(), CompileError> {
+        self.scopes.push(FunctionScope::default());
+        self.symbols.enter();
+        for param in params {
+            self.symbols.define_param(param)?;
+        }
+        self.declare(&body.statements)?;
+        self.block(&body.statements)?;
+        self.emit(Opcode::ReturnValue, &[]);
+        if self.offset() > MAX_OPERAND {
+            return Err(CompileError::FunctionTooLarge);
+        }
+
+        let (num_locals, free) = self.symbols.leave();
+        let function = self.finish_function(name, params.len(), num_locals);
+        debug!("compiled {} with {} free variables", function, free.len());
+        if self.constants.len() == MAX_CONSTANTS {
+            return Err(CompileError::TooManyConstants);
+        }
+        self.constants.push(Constant::Function(Rc::new(function)));
+
+        self.emit(Opcode::Closure, &[self.constants.len() - 1, free.len()]);
+        for symbol in free {
+            let is_local = symbol.scope == Scope::Local;
+            let capture = [u8::from(is_local), symbol.index as u8];
+            self.scope().instructions.extend(&capture);
+        }
+        Ok(())
+    }
+
+    /// Binds the names of all `let`s of the function up front, including those in nested blocks
+    /// which share its scope in the evaluator. A function defined before a `let` can then refer
+    /// to that binding, which is set by the time the function is called.
+    fn declare(&mut self, statements: &[Spanned<Statement>]) -> Result<(), CompileError> {
+        for stmt in statements {
+            match &stmt.node {
+                Statement::Let { ident, value, .. } => {
+                    self.symbols.define(ident)?;
+                    self.declare_in(value)?;
+                }
+                Statement::Return(expr) | Statement::Expression(expr) => self.declare_in(expr)?,
+                Statement::Error => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares the `let`s in the blocks of the expression, but not in nested functions
+    fn declare_in(&mut self, expr: &Expression) -> Result<(), CompileError> {
+        match expr {
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.declare_in(condition)?;
+                self.declare(&consequence.statements)?;
+                if let Some(alternative) = alternative {
+                    self.declare(&alternative.statements)?;
+                }
+            }
+            Expression::Prefix { rhs, .. } => self.declare_in(rhs)?,
+            Expression::Infix { lhs, rhs, .. } => {
+                self.declare_in(lhs)?;
+                self.declare_in(rhs)?;
+            }
+            Expression::Assign { value, .. } => self.declare_in(value)?,
+            Expression::Call { function, args, .. } => {
+                self.declare_in(function)?;
+                for arg in args {
+                    self.declare_in(arg)?;
+                }
+            }
+            Expression::Array(items) => {
+                for item in items {
+                    self.declare_in(item)?;
+                }
+            }
+            Expression::Index { left, index } => {
+                self.declare_in(left)?;
+                self.declare_in(index)?;
+            }
+            Expression::Hash(pairs) => {
+                for (key, value) in pairs {
+                    self.declare_in(key)?;
+                    self.declare_in(value)?;
+                }
+            }
+            Expression::NumberLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::StringLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::Identifier(_)
+            | Expression::Function { .. } => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::read_instruction;
+    use crate::parser::parse;
+
+    fn test_compile(input: &str) -> Bytecode {
+        compile(&parse(input).unwrap()).unwrap()
+    }
+
+    /// The opcodes of the instructions, with their operands
+    fn ops(code: &[u8]) -> Vec<(Opcode, [usize; 2])> {
+        let mut ops = Vec::new();
+        let mut offset = 0;
+        while offset < code.len() {
+            let instruction = read_instruction(code, offset).unwrap();
+            ops.push((instruction.op, instruction.operands));
+            offset = instruction.next;
+        }
+        ops
+    }
+
+    #[test]
+    fn compile_arithmetic() {
+        let bytecode = test_compile("1 + 2 * 3;");
+        assert_eq!(
+            bytecode.constants,
+            [Constant::Integer(1), Constant::Integer(2), Constant::Integer(3)]
+        );
+        assert_eq!(
+            ops(&bytecode.main.instructions),
+            [
+                (Opcode::Constant, [0, 0]),
+                (Opcode::Constant, [1, 0]),
+                (Opcode::Constant, [2, 0]),
+                (Opcode::Mul, [0, 0]),
+                (Opcode::Add, [0, 0]),
+                (Opcode::ReturnValue, [0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_conditional() {
+        let bytecode = test_compile("if (true) { 10 }; 20;");
+        assert_eq!(
+            ops(&bytecode.main.instructions),
+            [
+                (Opcode::True, [0, 0]),
+                (Opcode::JumpIfFalse, [10, 0]),
+                (Opcode::Constant, [0, 0]),
+                (Opcode::Jump, [11, 0]),
+                (Opcode::Null, [0, 0]),
+                (Opcode::Pop, [0, 0]),
+                (Opcode::Constant, [1, 0]),
+                (Opcode::ReturnValue, [0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_globals_and_locals() {
+        let bytecode = test_compile("let a = 1; let f = fn(b) { let c = a; b + c };");
+        // `c` falls back to the global until its `let` runs
+        assert_eq!(bytecode.globals, ["a", "c", "f"]);
+        let function = match &bytecode.constants[1] {
+            Constant::Function(function) => function.clone(),
+            other => panic!("expected a function, got {:?}", other),
+        };
+        assert_eq!(function.name.as_deref(), Some("f"));
+        assert_eq!((function.num_params, function.num_locals), (1, 2));
+        assert_eq!(
+            ops(&function.instructions),
+            [
+                (Opcode::GetGlobal, [0, 0]),
+                (Opcode::SetLocal, [1, 0]),
+                (Opcode::GetLocal, [0, 0]),
+                (Opcode::TryGetLocal, [1, 14]),
+                (Opcode::GetGlobal, [1, 0]),
+                (Opcode::Add, [0, 0]),
+                (Opcode::ReturnValue, [0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_closures() {
+        let bytecode = test_compile("fn(a) { fn(b) { fn() { a + b } } };");
+        let code = |idx: usize| match &bytecode.constants[idx] {
+            Constant::Function(function) => function.instructions.clone(),
+            other => panic!("expected a function, got {:?}", other),
+        };
+        // the innermost function gets `a` through the middle one
+        assert_eq!(&code(1)[..6], [Opcode::Closure as u8, 0, 0, 2, 0, 0]);
+        assert_eq!(&code(1)[6..], [1, 0, Opcode::ReturnValue as u8]);
+        assert_eq!(&code(2)[..6], [Opcode::Closure as u8, 0, 1, 1, 1, 0]);
+        assert_eq!(
+            ops(&code(0)),
+            [
+                (Opcode::GetFree, [0, 0]),
+                (Opcode::GetFree, [1, 0]),
+                (Opcode::Add, [0, 0]),
+                (Opcode::ReturnValue, [0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_spans() {
+        let input = "let f = fn() {\n  1;\n  2 + x;\n};";
+        let bytecode = test_compile(input);
+        let function = match &bytecode.constants[2] {
+            Constant::Function(function) => function.clone(),
+            other => panic!("expected a function, got {:?}", other),
+        };
+        let span = function.span_at(5).unwrap();
+        assert_eq!(&input[span.as_range()], "2 + x;");
+        let span = bytecode.main.span_at(0).unwrap();
+        assert_eq!(&input[span.as_range()], input);
+    }
+
+    #[test]
+    fn compile_errors() {
+        let (program, _) = crate::parser::parse_with_errors("let = 5;");
+        assert_eq!(compile(&program).unwrap_err(), CompileError::ParseError);
+        let args = vec!["1"; 256].join(", ");
+        let program = parse(&format!("f({});", args)).unwrap();
+        assert_eq!(compile(&program).unwrap_err(), CompileError::TooManyArguments);
+    }
+}
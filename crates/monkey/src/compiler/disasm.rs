@@ -0,0 +1,168 @@
+use std::fmt::Write;
+
+use crate::common::{SourceFile, Span};
+use crate::lexer;
+
+use super::{read_instruction, Bytecode, CompiledFunction, Constant, Opcode};
+
+/// Lists the instructions of the program, followed by the functions it creates, which are
+/// indented under the function that creates them. With the source, every run of instructions is
+/// headed by the line it came from, otherwise by the span of the statement.
+pub fn disassemble(bytecode: &Bytecode, source: Option<&SourceFile>) -> String {
+    let mut out = String::new();
+    let header = "== <main> ==".to_string();
+    Listing {
+        bytecode,
+        source,
+        out: &mut out,
+    }
+    .function(&header, &bytecode.main, 0);
+    out
+}
+
+struct Listing<'a> {
+    bytecode: &'a Bytecode,
+    source: Option<&'a SourceFile>,
+    out: &'a mut String,
+}
+
+impl Listing<'_> {
+    fn function(&mut self, header: &str, function: &CompiledFunction, depth: usize) {
+        let indent = "  ".repeat(depth);
+        self.line(&indent, header);
+
+        let code = &function.instructions;
+        let mut nested = Vec::new();
+        let mut origin = None;
+        let mut offset = 0;
+        while offset < code.len() {
+            let span = function.span_at(offset);
+            if span.is_some() && self.origin(span) != origin {
+                origin = self.origin(span);
+                self.line(&indent, &format!("     {}", origin.as_deref().unwrap_or("")));
+            }
+
+            let instruction = match read_instruction(code, offset) {
+                Some(instruction) => instruction,
+                None => {
+                    let text = format!("{:04} <invalid byte 0x{:02x}>", offset, code[offset]);
+                    self.line(&indent, &text);
+                    break;
+                }
+            };
+            let op = instruction.op;
+            let widths = op.operand_widths();
+            let operands: Vec<_> = instruction.operands[..widths.len()]
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            let mut text = format!("{:04} {:<14}{}", offset, op, operands.join(" "));
+            let comment = match op {
+                Opcode::Constant | Opcode::Closure => self.constant(instruction.operands[0]),
+                Opcode::GetGlobal | Opcode::SetGlobal | Opcode::AssignGlobal => {
+                    self.global(instruction.operands[0])
+                }
+                _ => None,
+            };
+            if let Some(comment) = comment {
+                write!(text, "  ; {}", comment).unwrap();
+            }
+            if op == Opcode::Closure {
+                for capture in code[offset + 4..instruction.next].chunks(2) {
+                    let kind = if capture[0] == 1 { "local" } else { "free" };
+                    write!(text, ", {} {}", kind, capture[1]).unwrap();
+                }
+                nested.push(instruction.operands[0]);
+            }
+            self.line(&indent, text.trim_end());
+            offset = instruction.next;
+        }
+
+        for idx in nested {
+            if let Some(Constant::Function(function)) = self.bytecode.constants.get(idx) {
+                let header = format!(
+                    "== {} (constant {}, {} locals) ==",
+                    function, idx, function.num_locals
+                );
+                self.function(&header, function, depth + 1);
+            }
+        }
+    }
+
+    fn line(&mut self, indent: &str, text: &str) {
+        writeln!(self.out, "{}{}", indent, text).unwrap();
+    }
+
+    /// The numbered source line of the statement, or its span without the source
+    fn origin(&self, span: Option<Span>) -> Option<String> {
+        let span = span?;
+        Some(match self.source {
+            Some(source) => {
+                let line = source.line_index().line_col(span.start).line;
+                let text = source.line_text(line).unwrap_or("");
+                format!("{:>4} | {}", line, text.trim())
+            }
+            None => format!("@ {}", span),
+        })
+    }
+
+    fn constant(&self, idx: usize) -> Option<String> {
+        Some(match self.bytecode.constants.get(idx)? {
+            Constant::Integer(x) => x.to_string(),
+            Constant::Float(x) => format!("{:?}", x),
+            Constant::String(x) => format!("\"{}\"", lexer::escape(x)),
+            Constant::Function(function) => function.to_string(),
+        })
+    }
+
+    fn global(&self, idx: usize) -> Option<String> {
+        self.bytecode.globals.get(idx).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    #[test]
+    fn disassemble_program() {
+        let input = "let x = \"a\";\nlet f = fn(y) {\n  fn() { x + y }\n};\nf(1.5);";
+        let source = SourceFile::new("test.mk", input);
+        let bytecode = compile(&parse(input).unwrap()).unwrap();
+        let expected = r#"== <main> ==
+        1 | let x = "a";
+0000 Constant      0  ; "a"
+0003 SetGlobal     0  ; x
+        2 | let f = fn(y) {
+0006 Closure       2 0  ; compiled function f/1
+0010 SetGlobal     1  ; f
+        5 | f(1.5);
+0013 GetGlobal     1  ; f
+0016 Constant      3  ; 1.5
+0019 Call          1
+0021 ReturnValue
+  == compiled function f/1 (constant 2, 1 locals) ==
+          3 | fn() { x + y }
+  0000 Closure       1 1  ; compiled function/0, local 0
+  0006 ReturnValue
+    == compiled function/0 (constant 1, 0 locals) ==
+            3 | fn() { x + y }
+    0000 GetGlobal     0  ; x
+    0003 GetFree       0
+    0005 Add
+    0006 ReturnValue
+"#;
+        assert_eq!(disassemble(&bytecode, Some(&source)), expected);
+    }
+
+    #[test]
+    fn disassemble_without_source() {
+        let bytecode = compile(&parse("1;").unwrap()).unwrap();
+        assert_eq!(
+            disassemble(&bytecode, None),
+            "== <main> ==\n     @ 0..2\n0000 Constant      0  ; 1\n0003 ReturnValue\n"
+        );
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::common::Span;
+
+use super::{read_instruction, Bytecode, CompiledFunction, Constant, Opcode, SpanEntry};
+
+/// The first bytes of every bytecode file
+pub const MAGIC: &[u8; 4] = b"MKC\0";
+
+/// Changes whenever the layout of the file or the meaning of an instruction changes, files of
+/// other versions are rejected. See docs/bytecode.md.
+pub const FORMAT_VERSION: u16 = 2;
+
+const MAX_LOCALS: usize = 1 << 8;
+
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("Not a Monkey bytecode file")]
+    BadMagic,
+
+    #[error("Bytecode format version {found} is not supported, expected version {expected}")]
+    UnsupportedVersion { found: u16, expected: u16 },
+
+    #[error("Bytecode file is truncated")]
+    Truncated,
+
+    #[error("Bytecode file has {0} unexpected bytes at the end")]
+    TrailingBytes(usize),
+
+    #[error("Invalid bytecode: {0}")]
+    Invalid(String),
+}
+
+impl Bytecode {
+    /// Writes the bytecode in the versioned binary format that `read_from` loads
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut out = Encoder::default();
+        out.0.extend(MAGIC);
+        out.0.extend(&FORMAT_VERSION.to_be_bytes());
+
+        out.len(self.globals.len())?;
+        for name in &self.globals {
+            out.string(name)?;
+        }
+
+        // the function table starts with the top level, followed by every function constant
+        let mut functions = vec![&*self.main];
+        let mut constants = Vec::with_capacity(self.constants.len());
+        for constant in &self.constants {
+            if let Constant::Function(function) = constant {
+                constants.push(Some(functions.len()));
+                functions.push(function);
+            } else {
+                constants.push(None);
+            }
+        }
+
+        out.len(functions.len())?;
+        for function in functions {
+            out.function(function)?;
+        }
+
+        out.len(self.constants.len())?;
+        for (constant, function) in self.constants.iter().zip(constants) {
+            match constant {
+                Constant::Integer(x) => {
+                    out.0.push(0);
+                    out.0.extend(&x.to_be_bytes());
+                }
+                Constant::Float(x) => {
+                    out.0.push(1);
+                    out.0.extend(&x.to_bits().to_be_bytes());
+                }
+                Constant::String(x) => {
+                    out.0.push(2);
+                    out.string(x)?;
+                }
+                Constant::Function(_) => {
+                    out.0.push(3);
+                    out.len(function.expect("function constants are in the table"))?;
+                }
+            }
+        }
+
+        writer.write_all(&out.0)
+    }
+
+    /// Loads bytecode written by `write_to`. Everything an instruction refers to is checked, so
+    /// the vm can run whatever this returns.
+    pub fn read_from(reader: &mut impl Read) -> Result<Bytecode, FormatError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut input = Decoder { bytes: &bytes };
+
+        if input.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(FormatError::BadMagic);
+        }
+        let version = input.u16()?;
+        if version != FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion {
+                found: version,
+                expected: FORMAT_VERSION,
+            });
+        }
+
+        let mut globals = Vec::new();
+        for _ in 0..input.len(4)? {
+            globals.push(input.string()?);
+        }
+
+        let mut functions = Vec::new();
+        for _ in 0..input.len(17)? {
+            functions.push(input.function()?);
+        }
+
+        let mut constants = Vec::new();
+        for _ in 0..input.len(5)? {
+            constants.push(match input.u8()? {
+                0 => RawConstant::Integer(i64::from_be_bytes(input.array()?)),
+                1 => RawConstant::Float(f64::from_bits(u64::from_be_bytes(input.array()?))),
+                2 => RawConstant::String(input.string()?),
+                3 => RawConstant::Function(input.u32()?),
+                tag => return Err(invalid(format!("unknown constant kind {}", tag))),
+            });
+        }
+
+        if !input.bytes.is_empty() {
+            return Err(FormatError::TrailingBytes(input.bytes.len()));
+        }
+        validate(&functions, &constants, globals.len())?;
+
+        let mut functions: Vec<_> = functions.into_iter().map(Rc::new).collect();
+        let constants = constants
+            .into_iter()
+            .map(|constant| match constant {
+                RawConstant::Integer(x) => Constant::Integer(x),
+                RawConstant::Float(x) => Constant::Float(x),
+                RawConstant::String(x) => Constant::String(x),
+                RawConstant::Function(idx) => Constant::Function(functions[idx].clone()),
+            })
+            .collect();
+        Ok(Bytecode {
+            main: functions.swap_remove(0),
+            constants,
+            globals,
+        })
+    }
+}
+
+/// A constant whose function is still an index into the function table
+enum RawConstant {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Function(usize),
+}
+
+fn invalid(message: String) -> FormatError {
+    FormatError::Invalid(message)
+}
+
+/// Checks that every index in the instructions is in range and that the functions cannot run past
+/// their end. Jumps only go forward, like the compiler emits them.
+fn validate(
+    functions: &[CompiledFunction],
+    constants: &[RawConstant],
+    num_globals: usize,
+) -> Result<(), FormatError> {
+    if functions.is_empty() {
+        return Err(invalid("there is no top level function".into()));
+    }
+    if functions[0].num_params != 0 {
+        return Err(invalid("the top level function has parameters".into()));
+    }
+    for constant in constants {
+        if let RawConstant::Function(idx) = constant {
+            if *idx == 0 || *idx >= functions.len() {
+                return Err(invalid(format!("function constant refers to function {}", idx)));
+            }
+        }
+    }
+
+    // the number of free variables of a function is given by the closures that create it
+    let mut num_free = HashMap::new();
+    num_free.insert(0, 0);
+    for (function_idx, function) in functions.iter().enumerate() {
+        let code = &function.instructions;
+        let mut offset = 0;
+        while let Some(instruction) = read_instruction(code, offset) {
+            if instruction.op == Opcode::Closure {
+                if let Some(RawConstant::Function(idx)) = constants.get(instruction.operands[0]) {
+                    let count = *num_free.entry(*idx).or_insert(instruction.operands[1]);
+                    if count != instruction.operands[1] {
+                        return Err(invalid(format!(
+                            "function {} is created with different numbers of free variables",
+                            idx
+                        )));
+                    }
+                }
+            }
+            offset = instruction.next;
+        }
+        if offset < code.len() {
+            return Err(invalid(format!(
+                "function {} has an invalid instruction at offset {}",
+                function_idx, offset
+            )));
+        }
+    }
+
+    for (idx, function) in functions.iter().enumerate() {
+        let num_free = num_free.get(&idx).copied().unwrap_or(0);
+        validate_function(function, constants, num_globals, num_free)
+            .map_err(|message| invalid(format!("function {}: {}", idx, message)))?;
+    }
+    Ok(())
+}
+
+fn validate_function(
+    function: &CompiledFunction,
+    constants: &[RawConstant],
+    num_globals: usize,
+    num_free: usize,
+) -> Result<(), String> {
+    if function.num_locals > MAX_LOCALS || function.num_params > function.num_locals {
+        return Err(format!(
+            "{} parameters and {} locals",
+            function.num_params, function.num_locals
+        ));
+    }
+    let code = &function.instructions;
+    let mut starts = Vec::new();
+    let mut jumps = Vec::new();
+    let mut last = None;
+    let mut offset = 0;
+    while offset < code.len() {
+        let instruction = read_instruction(code, offset).expect("the instructions were decoded");
+        let operand = instruction.operands[0];
+        let in_range = match instruction.op {
+            Opcode::Constant => matches!(
+                constants.get(operand),
+                Some(RawConstant::Integer(_) | RawConstant::Float(_) | RawConstant::String(_))
+            ),
+            Opcode::Closure => {
+                let captures = &code[offset + 4..instruction.next];
+                matches!(constants.get(operand), Some(RawConstant::Function(_)))
+                    && captures.chunks(2).all(|capture| match capture[0] {
+                        0 => usize::from(capture[1]) < num_free,
+                        1 => usize::from(capture[1]) < function.num_locals,
+                        _ => false,
+                    })
+            }
+            Opcode::GetGlobal | Opcode::SetGlobal | Opcode::AssignGlobal => operand < num_globals,
+            Opcode::GetLocal | Opcode::SetLocal => operand < function.num_locals,
+            Opcode::GetFree | Opcode::SetFree => operand < num_free,
+            Opcode::Jump | Opcode::JumpIfFalse => {
+                jumps.push(operand);
+                operand > offset
+            }
+            Opcode::TryGetLocal | Opcode::TryAssignLocal => {
+                jumps.push(instruction.operands[1]);
+                operand < function.num_locals && instruction.operands[1] > offset
+            }
+            Opcode::TryGetFree | Opcode::TryAssignFree => {
+                jumps.push(instruction.operands[1]);
+                operand < num_free && instruction.operands[1] > offset
+            }
+            _ => true,
+        };
+        if !in_range {
+            return Err(format!("bad operand of {} at offset {}", instruction.op, offset));
+        }
+        starts.push(offset);
+        last = Some(instruction.op);
+        offset = instruction.next;
+    }
+
+    if last != Some(Opcode::ReturnValue) {
+        return Err("does not end with a return".into());
+    }
+    if let Some(jump) = jumps.iter().find(|jump| starts.binary_search(jump).is_err()) {
+        return Err(format!("jump to {} is not the start of an instruction", jump));
+    }
+    for entry in &function.spans {
+        if entry.start > entry.end || entry.end > code.len() || entry.span.start > entry.span.end {
+            return Err(format!("bad span {}..{}", entry.start, entry.end));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn len(&mut self, len: usize) -> io::Result<()> {
+        let len = u32::try_from(len).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "bytecode is too large to write")
+        })?;
+        self.0.extend(&len.to_be_bytes());
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str) -> io::Result<()> {
+        self.len(s.len())?;
+        self.0.extend(s.as_bytes());
+        Ok(())
+    }
+
+    /// The name is preceded by 1, or it is a single 0 for functions without a name
+    fn function(&mut self, function: &CompiledFunction) -> io::Result<()> {
+        match &function.name {
+            Some(name) => {
+                self.0.push(1);
+                self.string(name)?;
+            }
+            None => self.0.push(0),
+        }
+        self.len(function.num_params)?;
+        self.len(function.num_locals)?;
+        self.len(function.instructions.len())?;
+        self.0.extend(&function.instructions);
+        self.len(function.spans.len())?;
+        for entry in &function.spans {
+            for &value in &[entry.start, entry.end, entry.span.start, entry.span.end] {
+                self.len(value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        if len > self.bytes.len() {
+            return Err(FormatError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], FormatError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FormatError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<usize, FormatError> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    /// Reads the number of items that follow, each of them at least `min_size` bytes, so that a
+    /// corrupted count cannot make the reader allocate more than the file holds
+    fn len(&mut self, min_size: usize) -> Result<usize, FormatError> {
+        let len = self.u32()?;
+        if len.saturating_mul(min_size) > self.bytes.len() {
+            return Err(FormatError::Truncated);
+        }
+        Ok(len)
+    }
+
+    fn string(&mut self) -> Result<String, FormatError> {
+        let len = self.len(1)?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("a string is not valid UTF-8".into()))
+    }
+
+    fn function(&mut self) -> Result<CompiledFunction, FormatError> {
+        let name = match self.u8()? {
+            0 => None,
+            1 => Some(self.string()?),
+            tag => return Err(invalid(format!("unknown function name tag {}", tag))),
+        };
+        let num_params = self.u32()?;
+        let num_locals = self.u32()?;
+        let len = self.len(1)?;
+        let instructions = self.take(len)?.to_vec();
+        let mut spans = Vec::new();
+        for _ in 0..self.len(16)? {
+            let start = self.u32()?;
+            let end = self.u32()?;
+            let span = Span::new(self.u32()?, self.u32()?);
+            spans.push(SpanEntry { start, end, span });
+        }
+        Ok(CompiledFunction {
+            name,
+            num_params,
+            num_locals,
+            instructions,
+            spans,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    const PROGRAM: &str = "let x = [1.5, \"s\"];\nlet f = fn(a) { fn() { a + len(x) } };\nf(1)();";
+
+    fn to_bytes(bytecode: &Bytecode) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytecode.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn load(bytes: &[u8]) -> Result<Bytecode, FormatError> {
+        Bytecode::read_from(&mut &bytes[..])
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytecode = compile(&parse(PROGRAM).unwrap()).unwrap();
+        let bytes = to_bytes(&bytecode);
+        assert_eq!(&bytes[..6], b"MKC\0\0\x02");
+        assert_eq!(load(&bytes).unwrap(), bytecode);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let bytes = to_bytes(&compile(&parse(PROGRAM).unwrap()).unwrap());
+        assert!(matches!(load(b"MK"), Err(FormatError::BadMagic)));
+        assert!(matches!(load(b"#!/bin/monkey"), Err(FormatError::BadMagic)));
+
+        let mut newer = bytes.clone();
+        newer[5] = 3;
+        assert_eq!(
+            load(&newer).unwrap_err().to_string(),
+            "Bytecode format version 3 is not supported, expected version 2"
+        );
+
+        assert!(matches!(load(&bytes[..bytes.len() - 1]), Err(FormatError::Truncated)));
+        let mut longer = bytes;
+        longer.push(0);
+        assert!(matches!(load(&longer), Err(FormatError::TrailingBytes(1))));
+    }
+
+    #[test]
+    fn rejects_invalid_instructions() {
+        let mut bytecode = compile(&parse("let x = 1; x;").unwrap()).unwrap();
+        let main = Rc::get_mut(&mut bytecode.main).unwrap();
+        // `GetGlobal 0` becomes `GetGlobal 1`, and there is only one global
+        assert_eq!(main.instructions[6..9], [Opcode::GetGlobal as u8, 0, 0]);
+        main.instructions[8] = 1;
+        assert_eq!(
+            load(&to_bytes(&bytecode)).unwrap_err().to_string(),
+            "Invalid bytecode: function 0: bad operand of GetGlobal at offset 6"
+        );
+
+        let main = Rc::get_mut(&mut bytecode.main).unwrap();
+        main.instructions[8] = 0;
+        main.instructions.pop();
+        assert_eq!(
+            load(&to_bytes(&bytecode)).unwrap_err().to_string(),
+            "Invalid bytecode: function 0: does not end with a return"
+        );
+    }
+}
@@ -0,0 +1,15 @@
+mod bytecode;
+mod code;
+mod compile_error;
+#[allow(clippy::module_inception)]
+mod compiler;
+mod disasm;
+mod format;
+mod symbol_table;
+
+pub use bytecode::{Bytecode, CompiledFunction, Constant, SpanEntry};
+pub use code::{make, read_instruction, Instruction, Opcode};
+pub use compile_error::CompileError;
+pub use compiler::compile;
+pub use disasm::disassemble;
+pub use format::{FormatError, FORMAT_VERSION, MAGIC};
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::mem;
+
+use super::CompileError;
+
+const MAX_GLOBALS: usize = 1 << 16;
+const MAX_LOCALS: usize = 1 << 8;
+/// the count is an operand of `Closure`, so one less than the indices allow
+const MAX_FREE: usize = (1 << 8) - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Global,
+    Local,
+    /// captured from an enclosing function
+    Free,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol {
+    pub scope: Scope,
+    pub index: usize,
+}
+
+/// The names bound in a function, chained to the tables of the enclosing functions. The outermost
+/// table holds the globals.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    store: HashMap<String, Symbol>,
+    num_definitions: usize,
+    /// parameters come first, so the locals below this are always bound
+    num_params: usize,
+    /// the symbols in the enclosing function that the free variables refer to, by index
+    free: Vec<Symbol>,
+    /// the names of the globals by index, only used in the outermost table
+    global_names: Vec<String>,
+    outer: Option<Box<SymbolTable>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// Starts the table of a function nested in the current one
+    pub fn enter(&mut self) {
+        let outer = mem::take(self);
+        self.outer = Some(Box::new(outer));
+    }
+
+    /// Goes back to the enclosing table, returning the number of locals of the function that was
+    /// left and the symbols its free variables refer to
+    pub fn leave(&mut self) -> (usize, Vec<Symbol>) {
+        let outer = self.outer.take().expect("left the global scope");
+        let inner = mem::replace(self, *outer);
+        (inner.num_definitions, inner.free)
+    }
+
+    pub fn global_names(&self) -> &[String] {
+        &self.global_names
+    }
+
+    /// Binds the name in the current scope. Binding a name again reuses its slot, like the
+    /// evaluator replaces the binding in the environment.
+    pub fn define(&mut self, name: &str) -> Result<Symbol, CompileError> {
+        match self.store.get(name) {
+            Some(symbol) => Ok(*symbol),
+            None => self.define_new(name),
+        }
+    }
+
+    /// Binds a parameter of the current function. Parameters always get a new slot because they
+    /// are set in order, and they are bound for the whole call.
+    pub fn define_param(&mut self, name: &str) -> Result<Symbol, CompileError> {
+        let symbol = self.define_new(name)?;
+        self.num_params = self.num_definitions;
+        Ok(symbol)
+    }
+
+    fn define_new(&mut self, name: &str) -> Result<Symbol, CompileError> {
+        let symbol = match self.outer {
+            None if self.num_definitions == MAX_GLOBALS => return Err(CompileError::TooManyGlobals),
+            None => {
+                self.global_names.push(name.to_string());
+                Symbol {
+                    scope: Scope::Global,
+                    index: self.num_definitions,
+                }
+            }
+            Some(_) if self.num_definitions == MAX_LOCALS => return Err(CompileError::TooManyLocals),
+            Some(_) => Symbol {
+                scope: Scope::Local,
+                index: self.num_definitions,
+            },
+        };
+        self.num_definitions += 1;
+        self.store.insert(name.to_string(), symbol);
+        Ok(symbol)
+    }
+
+    /// Finds the bindings the name can refer to, innermost first. Like in the evaluator, a name
+    /// refers to the innermost binding that is set when it is used, so a `let` that has not run
+    /// yet falls back to the next one. The chain ends at a parameter, which is always set, or at
+    /// the global of that name. Names that are not bound anywhere are globals, because a function
+    /// can use a global that is only bound after the function is defined.
+    pub fn resolve(&mut self, name: &str) -> Result<Vec<Symbol>, CompileError> {
+        let own = self.store.get(name).copied();
+        let outer = match &mut self.outer {
+            Some(outer) => outer,
+            None => {
+                let symbol = match own {
+                    Some(symbol) => symbol,
+                    None => self.define_new(name)?,
+                };
+                return Ok(vec![symbol]);
+            }
+        };
+        if let Some(symbol) = own {
+            if symbol.index < self.num_params {
+                return Ok(vec![symbol]);
+            }
+        }
+
+        let mut chain: Vec<_> = own.into_iter().collect();
+        for symbol in outer.resolve(name)? {
+            chain.push(match symbol.scope {
+                Scope::Global => symbol,
+                _ => self.capture(symbol)?,
+            });
+        }
+        Ok(chain)
+    }
+
+    /// The free variable that refers to the symbol of the enclosing function
+    fn capture(&mut self, outer: Symbol) -> Result<Symbol, CompileError> {
+        let index = match self.free.iter().position(|&symbol| symbol == outer) {
+            Some(index) => index,
+            None if self.free.len() == MAX_FREE => return Err(CompileError::TooManyFreeVariables),
+            None => {
+                self.free.push(outer);
+                self.free.len() - 1
+            }
+        };
+        Ok(Symbol {
+            scope: Scope::Free,
+            index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(scope: Scope, index: usize) -> Symbol {
+        Symbol { scope, index }
+    }
+
+    #[test]
+    fn resolve_nested() {
+        let mut table = SymbolTable::new();
+        assert_eq!(table.define("a"), Ok(symbol(Scope::Global, 0)));
+        table.enter();
+        assert_eq!(table.define_param("b"), Ok(symbol(Scope::Local, 0)));
+        table.enter();
+        assert_eq!(table.define_param("c"), Ok(symbol(Scope::Local, 0)));
+        assert_eq!(table.resolve("a"), Ok(vec![symbol(Scope::Global, 0)]));
+        assert_eq!(table.resolve("b"), Ok(vec![symbol(Scope::Free, 0)]));
+        assert_eq!(table.resolve("c"), Ok(vec![symbol(Scope::Local, 0)]));
+        // unknown names become globals
+        assert_eq!(table.resolve("d"), Ok(vec![symbol(Scope::Global, 1)]));
+
+        assert_eq!(table.leave(), (1, vec![symbol(Scope::Local, 0)]));
+        assert_eq!(table.leave(), (1, vec![]));
+        assert_eq!(table.global_names(), ["a", "d"]);
+    }
+
+    #[test]
+    fn define_again() {
+        let mut table = SymbolTable::new();
+        table.enter();
+        assert_eq!(table.define_param("a"), Ok(symbol(Scope::Local, 0)));
+        assert_eq!(table.define_param("a"), Ok(symbol(Scope::Local, 1)));
+        assert_eq!(table.define("a"), Ok(symbol(Scope::Local, 1)));
+        assert_eq!(table.resolve("a"), Ok(vec![symbol(Scope::Local, 1)]));
+    }
+
+    #[test]
+    fn resolve_falls_back_to_outer_bindings() {
+        let mut table = SymbolTable::new();
+        table.define("a").unwrap();
+        table.enter();
+        table.define_param("b").unwrap();
+        table.define("a").unwrap();
+        table.define("b").unwrap();
+        table.enter();
+        table.define("a").unwrap();
+        // the local `a` might not be set yet, then the one of the enclosing function is used,
+        // and then the global
+        assert_eq!(
+            table.resolve("a"),
+            Ok(vec![
+                symbol(Scope::Local, 0),
+                symbol(Scope::Free, 0),
+                symbol(Scope::Global, 0)
+            ])
+        );
+        // a `let` of a parameter's name replaces it, so it is always set
+        assert_eq!(table.resolve("b"), Ok(vec![symbol(Scope::Free, 1)]));
+        assert_eq!(table.leave(), (1, vec![symbol(Scope::Local, 1), symbol(Scope::Local, 0)]));
+    }
+
+    #[test]
+    fn free_variables_through_two_functions() {
+        let mut table = SymbolTable::new();
+        table.enter();
+        table.define_param("a").unwrap();
+        table.enter();
+        table.enter();
+        assert_eq!(table.resolve("a"), Ok(vec![symbol(Scope::Free, 0)]));
+        assert_eq!(table.resolve("a"), Ok(vec![symbol(Scope::Free, 0)]));
+        assert_eq!(table.leave(), (0, vec![symbol(Scope::Free, 0)]));
+        assert_eq!(table.leave(), (0, vec![symbol(Scope::Local, 0)]));
+    }
+}
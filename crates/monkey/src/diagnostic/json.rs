@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+use super::{Diagnostic, Label, LabelStyle};
+use crate::common::{LineCol, SourceMap, Span};
+
+/// A diagnostic with its labels resolved against a source map, so it can be serialized without
+/// access to the sources.
+#[derive(Debug, Serialize)]
+pub struct JsonDiagnostic<'a> {
+    pub severity: &'static str,
+    pub message: &'a str,
+    pub labels: Vec<JsonLabel<'a>>,
+    pub children: Vec<JsonChild<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonLabel<'a> {
+    /// `"primary"` or `"secondary"`
+    pub style: &'static str,
+    pub file: &'a str,
+    pub span: Span,
+    pub start: LineCol,
+    pub end: LineCol,
+    pub message: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonChild<'a> {
+    pub severity: &'static str,
+    pub message: &'a str,
+}
+
+impl Diagnostic {
+    pub fn to_json<'a>(&'a self, map: &'a SourceMap) -> JsonDiagnostic<'a> {
+        JsonDiagnostic {
+            severity: self.severity.as_str(),
+            message: &self.message,
+            labels: self
+                .labels
+                .iter()
+                .map(|label| JsonLabel::new(map, label))
+                .collect(),
+            children: self
+                .children
+                .iter()
+                .map(|(severity, message)| JsonChild {
+                    severity: severity.as_str(),
+                    message,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a> JsonLabel<'a> {
+    fn new(map: &'a SourceMap, label: &'a Label) -> JsonLabel<'a> {
+        let file = map.get(label.file);
+        JsonLabel {
+            style: match label.style {
+                LabelStyle::Primary => "primary",
+                LabelStyle::Secondary => "secondary",
+            },
+            file: file.name(),
+            span: label.span,
+            start: file.line_index().line_byte_col(label.span.start),
+            end: file.line_index().line_byte_col(label.span.end),
+            message: &label.message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::ToDiagnostic;
+    use crate::parser::parse;
+
+    #[test]
+    fn parse_error_json() {
+        let mut map = SourceMap::new();
+        let file = map.add("test.mk", "let x = 1;\nlet = 2;");
+        let err = parse(map.get(file).source()).unwrap_err();
+        let json = serde_json::to_value(err.to_diagnostic(file).to_json(&map)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "severity": "error",
+                "message": "Expected identifier, got `=`",
+                "labels": [{
+                    "style": "primary",
+                    "file": "test.mk",
+                    "span": { "start": 15, "end": 16 },
+                    "start": { "line": 2, "col": 5 },
+                    "end": { "line": 2, "col": 6 },
+                    "message": "",
+                }],
+                "children": [],
+            })
+        );
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(feature = "serde")]
+mod json;
+mod render;
+
+use std::fmt;
+
+use crate::common::{FileId, Span, Spanned};
+use crate::lexer::{EscapeError, LexError};
+use crate::parser::ParseError;
+
+#[cfg(feature = "serde")]
+pub use json::{JsonChild, JsonDiagnostic, JsonLabel};
+pub use render::Renderer;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Help,
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Help => "help",
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Whether a label points at the cause of the diagnostic or only gives extra context
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum LabelStyle {
+    Primary,
+    Secondary,
+}
+
+/// A span of source code in a file with an optional message attached to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub style: LabelStyle,
+    pub file: FileId,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn primary(file: FileId, span: Span) -> Label {
+        Label::new(LabelStyle::Primary, file, span)
+    }
+
+    pub fn secondary(file: FileId, span: Span) -> Label {
+        Label::new(LabelStyle::Secondary, file, span)
+    }
+
+    fn new(style: LabelStyle, file: FileId, span: Span) -> Label {
+        Label {
+            style,
+            file,
+            span,
+            message: String::new(),
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Label {
+        self.message = message.into();
+        self
+    }
+}
+
+/// An error, warning or note about the source code that can be rendered with `Renderer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    /// trailing notes and help messages that are not attached to a span
+    pub children: Vec<(Severity, String)>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            labels: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    pub fn with_label(mut self, label: Label) -> Diagnostic {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.children.push((Severity::Note, note.into()));
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.children.push((Severity::Help, help.into()));
+        self
+    }
+
+    /// The first primary label, used as the location of the diagnostic
+    pub fn primary_label(&self) -> Option<&Label> {
+        self.labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+    }
+}
+
+/// Errors that can be turned into a diagnostic pointing into the given file
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self, file: FileId) -> Diagnostic;
+}
+
+impl ToDiagnostic for Spanned<ParseError> {
+    fn to_diagnostic(&self, file: FileId) -> Diagnostic {
+        let label = Label::primary(file, self.span);
+        let diagnostic = Diagnostic::error(self.node.to_string());
+        match &self.node {
+            ParseError::ExpectedSemicolon { .. } | ParseError::NoSemicolon => diagnostic
+                .with_label(label.with_message("expected `;`"))
+                .with_help("statements must end with a semicolon"),
+            ParseError::UnexpectedToken { expected, .. } if expected.len() == 1 => {
+                diagnostic.with_label(label.with_message(format!("expected `{}`", expected[0])))
+            }
+            ParseError::UnexpectedEof => {
+                diagnostic.with_label(label.with_message("input ends here"))
+            }
+            ParseError::BadString(EscapeError::Unterminated) => {
+                diagnostic.with_label(label.with_message("string starts here"))
+            }
+            ParseError::Lex(LexError::UnterminatedComment) => diagnostic
+                .with_label(label.with_message("comment starts here"))
+                .with_help("block comments nest, every `/*` needs its own `*/`"),
+            _ => diagnostic.with_label(label),
+        }
+    }
+}
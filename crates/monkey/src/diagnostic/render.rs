@@ -0,0 +1,376 @@
+use std::fmt::Write;
+
+use super::{Diagnostic, Label, LabelStyle, Severity};
+use crate::common::{SourceFile, SourceMap};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+const TAB_WIDTH: usize = 4;
+
+/// Renders diagnostics rustc style, with the offending source lines and underlined labels.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    /// A renderer that outputs plain text, for logs and tests
+    pub fn plain() -> Renderer {
+        Renderer { color: false }
+    }
+
+    /// A renderer that outputs ansi colors, for terminals
+    pub fn colored() -> Renderer {
+        Renderer { color: true }
+    }
+
+    pub fn render(&self, map: &SourceMap, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        self.write(&mut out, map, diagnostic)
+            .expect("writing to a string cannot fail");
+        out
+    }
+
+    pub fn write(
+        &self,
+        out: &mut impl Write,
+        map: &SourceMap,
+        diagnostic: &Diagnostic,
+    ) -> std::fmt::Result {
+        let severity_color = severity_color(diagnostic.severity);
+        writeln!(
+            out,
+            "{}{}{}: {}{}{}",
+            self.paint(severity_color),
+            diagnostic.severity,
+            self.paint(RESET),
+            self.paint_bold(),
+            diagnostic.message,
+            self.paint(RESET),
+        )?;
+
+        let gutter_width = diagnostic
+            .labels
+            .iter()
+            .map(|label| {
+                let file = map.get(label.file);
+                file.line_index().line_col(label.span.end).line
+            })
+            .max()
+            .unwrap_or(0)
+            .to_string()
+            .len();
+        let gutter = " ".repeat(gutter_width);
+
+        // labels are grouped by file in the order the files first appear
+        let mut files = Vec::new();
+        for label in &diagnostic.labels {
+            if !files.contains(&label.file) {
+                files.push(label.file);
+            }
+        }
+
+        for (idx, &file_id) in files.iter().enumerate() {
+            let file = map.get(file_id);
+            let mut labels: Vec<_> = diagnostic
+                .labels
+                .iter()
+                .filter(|label| label.file == file_id)
+                .collect();
+            labels.sort_by_key(|label| (label.span.start, label.style == LabelStyle::Secondary));
+
+            // counts chars like the caret line, which only differs by expanding tabs
+            let location = file.location(labels[0].span.start);
+            let arrow = if idx == 0 { "-->" } else { ":::" };
+            writeln!(
+                out,
+                "{}{}{}{} {}",
+                gutter,
+                self.paint(BLUE),
+                arrow,
+                self.paint(RESET),
+                location
+            )?;
+            self.write_gutter(out, &gutter)?;
+            writeln!(out)?;
+            self.write_lines(out, file, &labels, diagnostic.severity, &gutter)?;
+        }
+
+        if !diagnostic.labels.is_empty() && !diagnostic.children.is_empty() {
+            self.write_gutter(out, &gutter)?;
+            writeln!(out)?;
+        }
+        for (severity, message) in &diagnostic.children {
+            writeln!(
+                out,
+                "{} {}={} {}{}{}: {}",
+                gutter,
+                self.paint(BLUE),
+                self.paint(RESET),
+                self.paint_bold(),
+                severity,
+                self.paint(RESET),
+                message
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_lines(
+        &self,
+        out: &mut impl Write,
+        file: &SourceFile,
+        labels: &[&Label],
+        severity: Severity,
+        gutter: &str,
+    ) -> std::fmt::Result {
+        let index = file.line_index();
+        let mut lines: Vec<usize> = labels
+            .iter()
+            .map(|label| index.line_col(label.span.start).line)
+            .collect();
+        lines.dedup();
+
+        for line in lines {
+            let text = file.line_text(line).unwrap_or("");
+            let line_span = index.line_span(line).unwrap_or_default();
+            writeln!(
+                out,
+                "{}{:>width$} |{} {}",
+                self.paint(BLUE),
+                line,
+                self.paint(RESET),
+                expand_tabs(text),
+                width = gutter.len()
+            )?;
+
+            for label in labels
+                .iter()
+                .filter(|label| index.line_col(label.span.start).line == line)
+            {
+                // multi line spans are only underlined until the end of their first line
+                let start = label.span.start - line_span.start;
+                let end = label.span.end.min(line_span.end).max(label.span.start) - line_span.start;
+                let (start, end) = (char_boundary(text, start), char_boundary(text, end));
+                let padding = display_width(&text[..start]);
+                let width = display_width(&text[start..end]).max(1);
+                let (marker, color) = match label.style {
+                    LabelStyle::Primary => ("^", severity_color(severity)),
+                    LabelStyle::Secondary => ("-", BLUE),
+                };
+
+                self.write_gutter(out, gutter)?;
+                write!(
+                    out,
+                    " {}{}{}",
+                    " ".repeat(padding),
+                    self.paint(color),
+                    marker.repeat(width)
+                )?;
+                if !label.message.is_empty() {
+                    write!(out, " {}", label.message)?;
+                }
+                writeln!(out, "{}", self.paint(RESET))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_gutter(&self, out: &mut impl Write, gutter: &str) -> std::fmt::Result {
+        write!(out, "{} {}|{}", gutter, self.paint(BLUE), self.paint(RESET))
+    }
+
+    fn paint(&self, color: &'static str) -> &'static str {
+        if self.color {
+            color
+        } else {
+            ""
+        }
+    }
+
+    fn paint_bold(&self) -> &'static str {
+        self.paint(BOLD)
+    }
+}
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+        Severity::Note => GREEN,
+        Severity::Help => CYAN,
+    }
+}
+
+fn expand_tabs(s: &str) -> String {
+    s.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// The offset clamped to the string and moved back to the start of the char it is in, so that
+/// labels with spans from another source cannot split a char
+fn char_boundary(s: &str, offset: usize) -> usize {
+    let mut offset = offset.min(s.len());
+    while !s.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// The number of columns the string takes up in a terminal, assuming every char is one column
+/// wide except tabs
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Span;
+    use crate::diagnostic::ToDiagnostic;
+    use crate::parser::parse;
+
+    fn render(source: &str, diagnostic: impl FnOnce(crate::common::FileId) -> Diagnostic) -> String {
+        let mut map = SourceMap::new();
+        let file = map.add("main.mk", source);
+        Renderer::plain().render(&map, &diagnostic(file))
+    }
+
+    #[test]
+    fn render_primary_label() {
+        let res = render("let x = 5;\nlet y = x +;\n", |file| {
+            Diagnostic::error("expected an expression")
+                .with_label(Label::primary(file, Span::new(21, 22)).with_message("here"))
+        });
+        assert_eq!(
+            res,
+            "\
+error: expected an expression
+ --> main.mk:2:11
+  |
+2 | let y = x +;
+  |           ^ here
+"
+        );
+    }
+
+    #[test]
+    fn render_secondary_labels_and_notes() {
+        let res = render("let x = 5;\nlet x = true;\n", |file| {
+            Diagnostic::warning("`x` is shadowed")
+                .with_label(Label::secondary(file, Span::new(4, 5)).with_message("first defined here"))
+                .with_label(Label::primary(file, Span::new(15, 16)).with_message("shadowed here"))
+                .with_note("shadowing is allowed")
+                .with_help("rename one of the bindings")
+        });
+        assert_eq!(
+            res,
+            "\
+warning: `x` is shadowed
+ --> main.mk:1:5
+  |
+1 | let x = 5;
+  |     - first defined here
+2 | let x = true;
+  |     ^ shadowed here
+  |
+  = note: shadowing is allowed
+  = help: rename one of the bindings
+"
+        );
+    }
+
+    #[test]
+    fn render_multiple_files() {
+        let mut map = SourceMap::new();
+        let main = map.add("main.mk", "let x = lib;");
+        let lib = map.add("lib.mk", "let lib = 5;");
+        let diagnostic = Diagnostic::error("bad import")
+            .with_label(Label::primary(main, Span::new(8, 11)))
+            .with_label(Label::secondary(lib, Span::new(4, 7)).with_message("defined here"));
+        assert_eq!(
+            Renderer::plain().render(&map, &diagnostic),
+            "\
+error: bad import
+ --> main.mk:1:9
+  |
+1 | let x = lib;
+  |         ^^^
+ ::: lib.mk:1:5
+  |
+1 | let lib = 5;
+  |     --- defined here
+"
+        );
+    }
+
+    #[test]
+    fn render_unicode_line() {
+        let source = "let Здравствуйте = ;";
+        let res = render(source, |file| {
+            let start = source.find(';').unwrap();
+            Diagnostic::error("expected an expression")
+                .with_label(Label::primary(file, Span::new(start, start + 1)))
+        });
+        // the column in the header is the one the caret is under
+        assert_eq!(
+            res,
+            "\
+error: expected an expression
+ --> main.mk:1:20
+  |
+1 | let Здравствуйте = ;
+  |                    ^
+"
+        );
+    }
+
+    #[test]
+    fn render_span_inside_a_char() {
+        // a span that does not fit the source, like one from another file, must not panic
+        let res = render("let xéé = 1;", |file| {
+            Diagnostic::error("mismatched span").with_label(Label::primary(file, Span::new(6, 9)))
+        });
+        assert!(res.contains("1 | let xéé = 1;\n  |      ^^\n"), "{}", res);
+    }
+
+    #[test]
+    fn render_parse_error() {
+        let source = "1234 true;";
+        let err = parse(source).unwrap_err();
+        let mut map = SourceMap::new();
+        let file = map.add("main.mk", source);
+        let res = Renderer::plain().render(&map, &err.to_diagnostic(file));
+        assert_eq!(
+            res,
+            "\
+error: Expected `;`, got `true`
+ --> main.mk:1:6
+  |
+1 | 1234 true;
+  |      ^^^^ expected `;`
+"
+        );
+    }
+
+    #[test]
+    fn render_colored() {
+        let res = render("5", |file| {
+            Diagnostic::error("bad").with_label(Label::primary(file, Span::new(0, 1)))
+        });
+        assert!(!res.contains('\x1b'));
+
+        let mut map = SourceMap::new();
+        let file = map.add("main.mk", "5");
+        let diagnostic = Diagnostic::error("bad").with_label(Label::primary(file, Span::new(0, 1)));
+        let res = Renderer::colored().render(&map, &diagnostic);
+        assert!(res.starts_with("\x1b[1;31merror\x1b[0m: "));
+    }
+}
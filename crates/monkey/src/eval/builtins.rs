@@ -0,0 +1,174 @@
+use std::fmt;
+
+use super::Object;
+
+/// A function that is provided by the interpreter instead of being written in Monkey
+pub struct Builtin {
+    pub name: &'static str,
+    /// the number of arguments, or `None` if it takes any number
+    pub arity: Option<usize>,
+    func: fn(&[Object]) -> Object,
+}
+
+/// Every builtin, looked up by name when an identifier is not bound in the environment. This is a
+/// static so that every `&Builtin` points into the same table.
+pub static BUILTINS: &[Builtin] = &[
+    Builtin::new("len", Some(1), len),
+    Builtin::new("first", Some(1), first),
+    Builtin::new("last", Some(1), last),
+    Builtin::new("rest", Some(1), rest),
+    Builtin::new("push", Some(2), push),
+    Builtin::new("puts", None, puts),
+    Builtin::new("type_of", Some(1), type_of),
+    Builtin::new("str", Some(1), str),
+    Builtin::new("int", Some(1), int),
+];
+
+/// Finds the builtin with the name
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+impl Builtin {
+    const fn new(name: &'static str, arity: Option<usize>, func: fn(&[Object]) -> Object) -> Self {
+        Builtin { name, arity, func }
+    }
+
+    /// Checks the number of arguments and calls the builtin
+    pub fn call(&self, args: &[Object]) -> Object {
+        match self.arity {
+            Some(arity) if arity != args.len() => Object::Error(format!(
+                "wrong number of arguments: expected {}, got {}",
+                arity,
+                args.len()
+            )),
+            _ => (self.func)(args),
+        }
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builtin").field("name", &self.name).finish()
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Builtin) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "builtin function {}", self.name)
+    }
+}
+
+fn unsupported(name: &str, arg: &Object) -> Object {
+    Object::Error(format!(
+        "argument to `{}` not supported, got {}",
+        name,
+        arg.type_name()
+    ))
+}
+
+fn len(args: &[Object]) -> Object {
+    let len = match &args[0] {
+        Object::String(s) => s.chars().count(),
+        Object::Array(items) => items.len(),
+        Object::Hash(pairs) => pairs.len(),
+        other => return unsupported("len", other),
+    };
+    Object::Integer(len as i64)
+}
+
+fn first(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(items) => items.first().cloned().unwrap_or(Object::Null),
+        other => unsupported("first", other),
+    }
+}
+
+fn last(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(items) => items.last().cloned().unwrap_or(Object::Null),
+        other => unsupported("last", other),
+    }
+}
+
+/// Everything but the first element, or `null` for an empty array
+fn rest(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(items) if items.is_empty() => Object::Null,
+        Object::Array(items) => Object::Array(items[1..].to_vec()),
+        other => unsupported("rest", other),
+    }
+}
+
+/// A new array with the element appended, the array itself is left as it is
+fn push(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Array(items) => {
+            let mut items = items.clone();
+            items.push(args[1].clone());
+            Object::Array(items)
+        }
+        other => unsupported("push", other),
+    }
+}
+
+/// Prints every argument on its own line
+fn puts(args: &[Object]) -> Object {
+    for arg in args {
+        println!("{}", arg);
+    }
+    Object::Null
+}
+
+fn type_of(args: &[Object]) -> Object {
+    Object::String(args[0].type_name().to_string())
+}
+
+fn str(args: &[Object]) -> Object {
+    Object::String(args[0].to_string())
+}
+
+/// Converts strings, floats and booleans to integers. Floats are truncated.
+fn int(args: &[Object]) -> Object {
+    match &args[0] {
+        Object::Integer(x) => Object::Integer(*x),
+        Object::Boolean(x) => Object::Integer(*x as i64),
+        // the bounds are exact powers of two, so they can be compared as floats
+        Object::Float(x) if x.is_finite() && *x >= i64::MIN as f64 && *x < -(i64::MIN as f64) => {
+            Object::Integer(*x as i64)
+        }
+        Object::Float(x) => Object::Error(format!("cannot convert {:?} to INTEGER", x)),
+        Object::String(s) => s.trim().parse().map_or_else(
+            |_| Object::Error(format!("cannot convert \"{}\" to INTEGER", s)),
+            Object::Integer,
+        ),
+        other => unsupported("int", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtins_are_unique() {
+        for builtin in BUILTINS {
+            assert!(std::ptr::eq(lookup(builtin.name).unwrap(), builtin));
+        }
+    }
+
+    #[test]
+    fn arity_is_checked() {
+        assert_eq!(
+            lookup("len").unwrap().call(&[]),
+            Object::Error("wrong number of arguments: expected 1, got 0".into())
+        );
+        assert_eq!(lookup("puts").unwrap().call(&[]), Object::Null);
+    }
+}
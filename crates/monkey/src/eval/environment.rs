@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::Object;
+
+/// A shared, mutable handle to an environment. Functions keep one to the environment they were
+/// defined in.
+pub type Env = Rc<RefCell<Environment>>;
+
+/// Maps names to values for one scope, falling back to the enclosing scope on lookup.
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Environment::default()))
+    }
+
+    /// Creates a new scope inside of `outer`
+    pub fn new_enclosed(outer: Env) -> Env {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            outer: Some(outer),
+        }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(obj) => Some(obj.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Binds the name in this scope, shadowing any binding of an enclosing scope
+    pub fn set(&mut self, name: impl Into<String>, obj: Object) {
+        self.store.insert(name.into(), obj);
+    }
+
+    /// Rebinds the name in the innermost scope that has it, returning false if no scope does
+    pub fn assign(&mut self, name: &str, obj: Object) -> bool {
+        match self.store.get_mut(name) {
+            Some(slot) => {
+                *slot = obj;
+                true
+            }
+            None => match &self.outer {
+                Some(outer) => outer.borrow_mut().assign(name, obj),
+                None => false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enclosed_lookup() {
+        let outer = Environment::new();
+        outer.borrow_mut().set("x", Object::Integer(1));
+        outer.borrow_mut().set("y", Object::Integer(2));
+
+        let inner = Environment::new_enclosed(outer.clone());
+        inner.borrow_mut().set("x", Object::Integer(10));
+
+        assert_eq!(inner.borrow().get("x"), Some(Object::Integer(10)));
+        assert_eq!(inner.borrow().get("y"), Some(Object::Integer(2)));
+        assert_eq!(inner.borrow().get("z"), None);
+        assert_eq!(outer.borrow().get("x"), Some(Object::Integer(1)));
+    }
+
+    #[test]
+    fn enclosed_assign() {
+        let outer = Environment::new();
+        outer.borrow_mut().set("x", Object::Integer(1));
+        outer.borrow_mut().set("y", Object::Integer(2));
+
+        let inner = Environment::new_enclosed(outer.clone());
+        inner.borrow_mut().set("x", Object::Integer(10));
+
+        assert!(inner.borrow_mut().assign("x", Object::Integer(11)));
+        assert!(inner.borrow_mut().assign("y", Object::Integer(3)));
+        assert!(!inner.borrow_mut().assign("z", Object::Integer(4)));
+
+        assert_eq!(inner.borrow().get("x"), Some(Object::Integer(11)));
+        assert_eq!(outer.borrow().get("x"), Some(Object::Integer(1)));
+        assert_eq!(outer.borrow().get("y"), Some(Object::Integer(3)));
+        assert_eq!(outer.borrow().get("z"), None);
+    }
+}
@@ -0,0 +1,814 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use log::debug;
+
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+use crate::common::{FileId, Span, Spanned};
+use crate::lexer::Token;
+
+use super::{builtins, Env, Environment, Function, HashKey, Object};
+
+/// How many function calls can be active at once before evaluation is aborted with an error.
+/// The evaluator also stops before it uses more than half of the stack it was given, see
+/// `Evaluator::with_stack_size`.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+/// The stack size the evaluator assumes unless it is told otherwise, which is what Rust gives to
+/// spawned threads
+pub const DEFAULT_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+/// A stack size that fits `MAX_CALL_DEPTH` nested calls of ordinary functions, even in a debug
+/// build
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+const STACK_OVERFLOW: &str = "stack overflow: too many nested function calls";
+
+/// Returns early from the enclosing function if the object is an error
+macro_rules! propagate {
+    ($obj:expr) => {
+        match $obj {
+            obj @ Object::Error(_) => return obj,
+            obj => obj,
+        }
+    };
+}
+
+/// Evaluates a program in a fresh environment
+pub fn eval(program: &Program) -> Object {
+    Evaluator::new().eval_program(program, &Environment::new())
+}
+
+/// Walks the AST and evaluates it to an `Object`. Runtime errors are returned as `Object::Error`.
+#[derive(Debug)]
+pub struct Evaluator {
+    depth: usize,
+    stack_size: usize,
+    /// where the stack was when evaluation started, see `stack_exhausted`
+    stack_base: Option<usize>,
+    /// the file of the code that is being evaluated
+    file: Option<FileId>,
+    error_span: Option<Span>,
+    error_file: Option<FileId>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator {
+            depth: 0,
+            stack_size: DEFAULT_STACK_SIZE,
+            stack_base: None,
+            file: None,
+            error_span: None,
+            error_file: None,
+        }
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator::default()
+    }
+
+    /// Lets recursion go deeper on a thread whose stack is `bytes` large. Without this the
+    /// evaluator assumes `DEFAULT_STACK_SIZE`, so that scripts cannot overflow the stack of the
+    /// thread that runs them.
+    pub fn with_stack_size(mut self, bytes: usize) -> Evaluator {
+        self.stack_size = bytes;
+        self
+    }
+
+    /// The span of the innermost statement that produced the last error, or of the call if a
+    /// native function failed
+    pub fn error_span(&self) -> Option<Span> {
+        self.error_span
+    }
+
+    /// The file that `error_span` points into, see `eval_program_in`
+    pub fn error_file(&self) -> Option<FileId> {
+        self.error_file
+    }
+
+    /// Like `eval_program`, for a program parsed from the file. Functions it defines remember the
+    /// file, so errors in them point into it even when a later program calls them.
+    pub fn eval_program_in(&mut self, file: FileId, program: &Program, env: &Env) -> Object {
+        self.file = Some(file);
+        let res = self.eval_program(program, env);
+        self.file = None;
+        res
+    }
+
+    /// Evaluates every statement in the environment and returns the value of the last one, or
+    /// the value of the first `return` statement
+    pub fn eval_program(&mut self, program: &Program, env: &Env) -> Object {
+        self.error_span = None;
+        self.error_file = None;
+        self.stack_base = None;
+        let mut res = Object::Null;
+        for stmt in program.statements() {
+            res = self.eval_statement(stmt, env);
+            match res {
+                Object::ReturnValue(value) => return *value,
+                Object::Error(_) => return res,
+                _ => (),
+            }
+        }
+        res
+    }
+
+    /// Like `eval_program`, except that return values are kept wrapped so that they can unwind
+    /// through nested blocks up to the function call
+    fn eval_block(&mut self, block: &BlockStatement, env: &Env) -> Object {
+        let mut res = Object::Null;
+        for stmt in &block.statements {
+            res = self.eval_statement(stmt, env);
+            if let Object::ReturnValue(_) | Object::Error(_) = res {
+                return res;
+            }
+        }
+        res
+    }
+
+    fn eval_statement(&mut self, stmt: &Spanned<Statement>, env: &Env) -> Object {
+        let res = self.eval_statement_kind(&stmt.node, env);
+        if res.is_error() && self.error_span.is_none() {
+            self.error_span = Some(stmt.span);
+            self.error_file = self.file;
+        }
+        res
+    }
+
+    fn eval_statement_kind(&mut self, stmt: &Statement, env: &Env) -> Object {
+        match stmt {
+            Statement::Let { ident, value, .. } => {
+                let value = propagate!(self.eval_expression(value, env));
+                env.borrow_mut().set(ident.as_str(), value);
+                Object::Null
+            }
+            Statement::Return(value) => {
+                let value = propagate!(self.eval_expression(value, env));
+                Object::ReturnValue(Box::new(value))
+            }
+            Statement::Expression(expr) => self.eval_expression(expr, env),
+            Statement::Error => Object::Error("cannot evaluate a statement that failed to parse".into()),
+        }
+    }
+
+    pub fn eval_expression(&mut self, expr: &Expression, env: &Env) -> Object {
+        if self.stack_exhausted() {
+            return Object::Error(STACK_OVERFLOW.into());
+        }
+        match expr {
+            Expression::NumberLiteral(x) => Object::Integer(*x),
+            Expression::FloatLiteral(x) => Object::Float(*x),
+            Expression::BooleanLiteral(x) => Object::Boolean(*x),
+            Expression::StringLiteral(x) => Object::String(x.clone()),
+            Expression::Identifier(name) => self.eval_identifier(name, env),
+            Expression::Prefix { prefix, rhs } => {
+                let rhs = propagate!(self.eval_expression(rhs, env));
+                eval_prefix(*prefix, rhs)
+            }
+            Expression::Infix {
+                lhs,
+                operator: operator @ (Token::And | Token::Or),
+                rhs,
+            } => {
+                // the right hand side is only evaluated if it decides the result
+                let lhs = propagate!(self.eval_expression(lhs, env));
+                if lhs.is_truthy() == (*operator == Token::Or) {
+                    return Object::Boolean(lhs.is_truthy());
+                }
+                let rhs = propagate!(self.eval_expression(rhs, env));
+                Object::Boolean(rhs.is_truthy())
+            }
+            Expression::Infix { lhs, operator, rhs } => {
+                let lhs = propagate!(self.eval_expression(lhs, env));
+                let rhs = propagate!(self.eval_expression(rhs, env));
+                eval_infix(*operator, lhs, rhs)
+            }
+            Expression::Assign {
+                ident,
+                operator,
+                value,
+            } => {
+                // the current value is read first, like the left hand side of an infix operator
+                let value = match operator.compound_operator() {
+                    Some(operator) => {
+                        let current = propagate!(self.eval_identifier(ident, env));
+                        let value = propagate!(self.eval_expression(value, env));
+                        propagate!(eval_infix(operator, current, value))
+                    }
+                    None => propagate!(self.eval_expression(value, env)),
+                };
+                if !env.borrow_mut().assign(ident, value.clone()) {
+                    return Object::Error(format!("identifier not found: {}", ident));
+                }
+                value
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let condition = propagate!(self.eval_expression(condition, env));
+                if condition.is_truthy() {
+                    self.eval_block(consequence, env)
+                } else if let Some(alternative) = alternative {
+                    self.eval_block(alternative, env)
+                } else {
+                    Object::Null
+                }
+            }
+            Expression::Function { params, body } => Object::Function(Rc::new(Function {
+                params: params.clone(),
+                body: body.clone(),
+                env: env.clone(),
+                file: self.file,
+            })),
+            Expression::Call {
+                function,
+                args,
+                span,
+            } => {
+                let function = propagate!(self.eval_expression(function, env));
+                let mut arg_values = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_values.push(propagate!(self.eval_expression(arg, env)));
+                }
+                let native = matches!(function, Object::Native(_));
+                let res = self.apply_function(function, arg_values);
+                // the host's errors point at the call, there is no statement inside of it
+                if native && res.is_error() {
+                    self.error_span = Some(*span);
+                    self.error_file = self.file;
+                }
+                res
+            }
+            Expression::Array(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(propagate!(self.eval_expression(item, env)));
+                }
+                Object::Array(values)
+            }
+            Expression::Index { left, index } => {
+                let left = propagate!(self.eval_expression(left, env));
+                let index = propagate!(self.eval_expression(index, env));
+                eval_index(left, index)
+            }
+            Expression::Hash(pairs) => {
+                let mut values = Vec::with_capacity(pairs.len());
+                for (key, value) in pairs {
+                    let key = propagate!(self.eval_expression(key, env));
+                    let value = propagate!(self.eval_expression(value, env));
+                    values.push((key, value));
+                }
+                build_hash(values)
+            }
+        }
+    }
+
+    /// Looks the name up in the environment, falling back to the builtins. This means builtins
+    /// can be shadowed by bindings of the same name.
+    pub fn eval_identifier(&self, name: &str, env: &Env) -> Object {
+        env.borrow()
+            .get(name)
+            .or_else(|| builtins::lookup(name).map(Object::Builtin))
+            .unwrap_or_else(|| Object::Error(format!("identifier not found: {}", name)))
+    }
+
+    /// Calls the function from outside of a program, like `eval_program` forgetting the span of
+    /// the previous error
+    pub fn call(&mut self, function: Object, args: Vec<Object>) -> Object {
+        self.error_span = None;
+        self.error_file = None;
+        self.stack_base = None;
+        self.apply_function(function, args)
+    }
+
+    pub fn apply_function(&mut self, function: Object, args: Vec<Object>) -> Object {
+        let function = match function {
+            Object::Function(function) => function,
+            Object::Builtin(builtin) => return builtin.call(&args),
+            Object::Native(native) => {
+                return native
+                    .call(&args)
+                    .unwrap_or_else(|err| Object::Error(err.message))
+            }
+            Object::Closure(_) => {
+                return Object::Error("compiled functions can only be called by the vm".into())
+            }
+            other => return Object::Error(format!("not a function: {}", other.type_name())),
+        };
+        if function.params.len() != args.len() {
+            return Object::Error(format!(
+                "wrong number of arguments: expected {}, got {}",
+                function.params.len(),
+                args.len()
+            ));
+        }
+        if self.depth >= MAX_CALL_DEPTH || self.stack_exhausted() {
+            return Object::Error(STACK_OVERFLOW.into());
+        }
+
+        let env = Environment::new_enclosed(function.env.clone());
+        for (param, arg) in function.params.iter().zip(args) {
+            env.borrow_mut().set(param.as_str(), arg);
+        }
+
+        self.depth += 1;
+        debug!("calling {} at depth {}", function, self.depth);
+        let caller_file = std::mem::replace(&mut self.file, function.file);
+        let res = self.eval_block(&function.body, &env);
+        self.file = caller_file;
+        self.depth -= 1;
+
+        match res {
+            Object::ReturnValue(value) => *value,
+            res => res,
+        }
+    }
+
+    /// Whether evaluation has used half of the stack since it started. The other half is left to
+    /// the host and to the work between two checks.
+    fn stack_exhausted(&mut self) -> bool {
+        let here = stack_address();
+        let base = *self.stack_base.get_or_insert(here);
+        base.abs_diff(here) > self.stack_size / 2
+    }
+}
+
+/// The address of a local variable, which tells how far the stack has grown
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    &marker as *const u8 as usize
+}
+
+pub(crate) fn eval_prefix(prefix: Token<'_>, rhs: Object) -> Object {
+    match (prefix, rhs) {
+        (Token::Bang, rhs) => Object::Boolean(!rhs.is_truthy()),
+        (Token::Minus, Object::Integer(x)) => x
+            .checked_neg()
+            .map_or_else(|| Object::Error("integer overflow".into()), Object::Integer),
+        (Token::Minus, Object::Float(x)) => Object::Float(-x),
+        (prefix, rhs) => Object::Error(format!("unknown operator: {}{}", prefix, rhs.type_name())),
+    }
+}
+
+/// Indexing out of bounds or with a missing key gives `null`
+pub(crate) fn eval_index(left: Object, index: Object) -> Object {
+    match (left, index) {
+        (Object::Array(items), Object::Integer(idx)) => usize::try_from(idx)
+            .ok()
+            .and_then(|idx| items.into_iter().nth(idx))
+            .unwrap_or(Object::Null),
+        (Object::Hash(pairs), key) => match HashKey::from_object(&key) {
+            Some(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            None => unusable_key(&key),
+        },
+        (left, index) => Object::Error(format!(
+            "index operator not supported: {}[{}]",
+            left.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+fn unusable_key(key: &Object) -> Object {
+    Object::Error(format!("unusable as hash key: {}", key.type_name()))
+}
+
+/// Builds a hash once every key and value has been evaluated, later pairs replace earlier ones
+pub(crate) fn build_hash(pairs: Vec<(Object, Object)>) -> Object {
+    let mut hash = BTreeMap::new();
+    for (key, value) in pairs {
+        match HashKey::from_object(&key) {
+            Some(key) => hash.insert(key, value),
+            None => return unusable_key(&key),
+        };
+    }
+    Object::Hash(hash)
+}
+
+pub(crate) fn eval_infix(operator: Token<'_>, lhs: Object, rhs: Object) -> Object {
+    match (lhs, rhs) {
+        (Object::Integer(lhs), Object::Integer(rhs)) => eval_integer_infix(operator, lhs, rhs),
+        // integers are promoted when they are mixed with floats
+        (Object::Float(lhs), Object::Float(rhs)) => eval_float_infix(operator, lhs, rhs),
+        (Object::Float(lhs), Object::Integer(rhs)) => eval_float_infix(operator, lhs, rhs as f64),
+        (Object::Integer(lhs), Object::Float(rhs)) => eval_float_infix(operator, lhs as f64, rhs),
+        (Object::Boolean(lhs), Object::Boolean(rhs)) => match operator {
+            Token::Eq => Object::Boolean(lhs == rhs),
+            Token::NotEq => Object::Boolean(lhs != rhs),
+            _ => Object::Error(format!("unknown operator: BOOLEAN {} BOOLEAN", operator)),
+        },
+        (Object::String(lhs), Object::String(rhs)) => match operator {
+            Token::Plus => Object::String(lhs + &rhs),
+            Token::Eq => Object::Boolean(lhs == rhs),
+            Token::NotEq => Object::Boolean(lhs != rhs),
+            _ => Object::Error(format!("unknown operator: STRING {} STRING", operator)),
+        },
+        (Object::Null, Object::Null) => match operator {
+            Token::Eq => Object::Boolean(true),
+            Token::NotEq => Object::Boolean(false),
+            _ => Object::Error(format!("unknown operator: NULL {} NULL", operator)),
+        },
+        (lhs, rhs) if lhs.type_name() != rhs.type_name() => Object::Error(format!(
+            "type mismatch: {} {} {}",
+            lhs.type_name(),
+            operator,
+            rhs.type_name()
+        )),
+        (lhs, rhs) => Object::Error(format!(
+            "unknown operator: {} {} {}",
+            lhs.type_name(),
+            operator,
+            rhs.type_name()
+        )),
+    }
+}
+
+fn eval_integer_infix(operator: Token<'_>, lhs: i64, rhs: i64) -> Object {
+    let overflow = || Object::Error("integer overflow".into());
+    match operator {
+        Token::Plus => lhs.checked_add(rhs).map_or_else(overflow, Object::Integer),
+        Token::Minus => lhs.checked_sub(rhs).map_or_else(overflow, Object::Integer),
+        Token::Asterisk => lhs.checked_mul(rhs).map_or_else(overflow, Object::Integer),
+        Token::Slash if rhs == 0 => Object::Error("division by zero".into()),
+        Token::Slash => lhs.checked_div(rhs).map_or_else(overflow, Object::Integer),
+        Token::Percent if rhs == 0 => Object::Error("division by zero".into()),
+        Token::Percent => lhs.checked_rem(rhs).map_or_else(overflow, Object::Integer),
+        Token::Lt => Object::Boolean(lhs < rhs),
+        Token::Gt => Object::Boolean(lhs > rhs),
+        Token::LtEq => Object::Boolean(lhs <= rhs),
+        Token::GtEq => Object::Boolean(lhs >= rhs),
+        Token::Eq => Object::Boolean(lhs == rhs),
+        Token::NotEq => Object::Boolean(lhs != rhs),
+        _ => Object::Error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    }
+}
+
+fn eval_float_infix(operator: Token<'_>, lhs: f64, rhs: f64) -> Object {
+    match operator {
+        Token::Plus => Object::Float(lhs + rhs),
+        Token::Minus => Object::Float(lhs - rhs),
+        Token::Asterisk => Object::Float(lhs * rhs),
+        Token::Slash => Object::Float(lhs / rhs),
+        Token::Percent => Object::Float(lhs % rhs),
+        Token::Lt => Object::Boolean(lhs < rhs),
+        Token::Gt => Object::Boolean(lhs > rhs),
+        Token::LtEq => Object::Boolean(lhs <= rhs),
+        Token::GtEq => Object::Boolean(lhs >= rhs),
+        Token::Eq => Object::Boolean(lhs == rhs),
+        Token::NotEq => Object::Boolean(lhs != rhs),
+        _ => Object::Error(format!("unknown operator: FLOAT {} FLOAT", operator)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn test_eval(input: &str) -> Object {
+        let program = parse(input).unwrap();
+        eval(&program)
+    }
+
+    fn test_eval_all(cases: &[(&str, Object)]) {
+        for (input, expected) in cases {
+            assert_eq!(&test_eval(input), expected, "input: {}", input);
+        }
+    }
+
+    fn error(message: &str) -> Object {
+        Object::Error(message.to_string())
+    }
+
+    #[test]
+    fn eval_integer() {
+        test_eval_all(&[
+            ("5;", Object::Integer(5)),
+            ("-10;", Object::Integer(-10)),
+            ("5 + 5 + 5 + 5 - 10;", Object::Integer(10)),
+            ("2 * 2 * 2 * 2 * 2;", Object::Integer(32)),
+            ("-50 + 100 + -50;", Object::Integer(0)),
+            ("20 + 2 * -10;", Object::Integer(0)),
+            ("50 / 2 * 2 + 10;", Object::Integer(60)),
+            ("3 * (3 * 3) + 10;", Object::Integer(37)),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10;", Object::Integer(50)),
+        ]);
+    }
+
+    #[test]
+    fn eval_boolean() {
+        test_eval_all(&[
+            ("true;", Object::Boolean(true)),
+            ("1 < 2;", Object::Boolean(true)),
+            ("1 > 2;", Object::Boolean(false)),
+            ("1 <= 1;", Object::Boolean(true)),
+            ("1 >= 2;", Object::Boolean(false)),
+            ("1 == 1;", Object::Boolean(true)),
+            ("1 != 1;", Object::Boolean(false)),
+            ("true == false;", Object::Boolean(false)),
+            ("(1 < 2) == true;", Object::Boolean(true)),
+            ("!true;", Object::Boolean(false)),
+            ("!!5;", Object::Boolean(true)),
+        ]);
+    }
+
+    #[test]
+    fn eval_float() {
+        test_eval_all(&[
+            ("1.5;", Object::Float(1.5)),
+            ("-2.5e1;", Object::Float(-25.0)),
+            ("1.5 + 1.5;", Object::Float(3.0)),
+            ("1 + 0.5;", Object::Float(1.5)),
+            ("3.0 / 2;", Object::Float(1.5)),
+            ("1.0 / 0;", Object::Float(f64::INFINITY)),
+            ("0.1 < 1;", Object::Boolean(true)),
+            ("2.0 == 2;", Object::Boolean(true)),
+            ("0x10 + 0b1 + 0o7 + 1_000;", Object::Integer(1024)),
+        ]);
+    }
+
+    #[test]
+    fn eval_string() {
+        test_eval_all(&[
+            (r#""hello";"#, Object::String("hello".into())),
+            (r#""a\tb";"#, Object::String("a\tb".into())),
+            (r#""hello" + " " + "world";"#, Object::String("hello world".into())),
+            (r#""a" == "a";"#, Object::Boolean(true)),
+            (r#""a" != "a";"#, Object::Boolean(false)),
+            (r#""a" - "b";"#, error("unknown operator: STRING - STRING")),
+            (r#""a" + 1;"#, error("type mismatch: STRING + INTEGER")),
+        ]);
+    }
+
+    #[test]
+    fn eval_collections() {
+        test_eval_all(&[
+            ("[1, 2 * 2, 3 + 3][1];", Object::Integer(4)),
+            ("let a = [1, [2, 3]]; a[1][0] + a[0];", Object::Integer(3)),
+            ("[1, 2][2];", Object::Null),
+            ("[1, 2][-1];", Object::Null),
+            (r#"{"a": 1, true: 2, 3: 3}["a"];"#, Object::Integer(1)),
+            (r#"let k = "b"; {"a" + k: 5}["ab"];"#, Object::Integer(5)),
+            ("{1: 1, 1: 2}[1];", Object::Integer(2)),
+            ("{}[true];", Object::Null),
+            ("1[0];", error("index operator not supported: INTEGER[INTEGER]")),
+            ("{}[[]];", error("unusable as hash key: ARRAY")),
+            ("{fn() {}: 1};", error("unusable as hash key: FUNCTION")),
+        ]);
+    }
+
+    #[test]
+    fn eval_collection_display() {
+        assert_eq!(test_eval(r#"[1, "a", [true]];"#).to_string(), r#"[1, "a", [true]]"#);
+        assert_eq!(
+            test_eval(r#"{"b": "x", "a": 1, 2: [], false: {}};"#).to_string(),
+            r#"{2: [], false: {}, "a": 1, "b": "x"}"#
+        );
+    }
+
+    #[test]
+    fn eval_logical() {
+        test_eval_all(&[
+            ("true && false;", Object::Boolean(false)),
+            ("true || false;", Object::Boolean(true)),
+            ("1 && \"a\";", Object::Boolean(true)),
+            ("false || 1 < 2 && 2 < 1;", Object::Boolean(false)),
+            // the right hand side would be an error if it was evaluated
+            ("false && undefined;", Object::Boolean(false)),
+            ("true || 1 / 0;", Object::Boolean(true)),
+            ("let x = 1; false && (x = 2); true || (x = 3); x;", Object::Integer(1)),
+            ("true && undefined;", error("identifier not found: undefined")),
+        ]);
+    }
+
+    #[test]
+    fn eval_modulo() {
+        test_eval_all(&[
+            ("7 % 3;", Object::Integer(1)),
+            ("-7 % 3;", Object::Integer(-1)),
+            ("2 + 7 % 3 * 2;", Object::Integer(4)),
+            ("7.5 % 2;", Object::Float(1.5)),
+            ("1 % 0;", error("division by zero")),
+        ]);
+    }
+
+    #[test]
+    fn eval_assign() {
+        test_eval_all(&[
+            ("let x = 1; x = 2; x;", Object::Integer(2)),
+            ("let x = 1; x = x + 1;", Object::Integer(2)),
+            ("let x = 1; let y = 2; x = y = 3; x + y;", Object::Integer(6)),
+            ("let x = 10; x += 5; x -= 1; x *= 2; x /= 4; x %= 4; x;", Object::Integer(3)),
+            (r#"let s = "a"; s += "b"; s;"#, Object::String("ab".into())),
+            (
+                "let n = 0; let inc = fn() { n += 1 }; inc(); inc(); n;",
+                Object::Integer(2),
+            ),
+            ("let x = 1; let f = fn(x) { x = 5 }; f(2); x;", Object::Integer(1)),
+            ("y = 1;", error("identifier not found: y")),
+            ("y += 1;", error("identifier not found: y")),
+            ("let x = true; x += 1;", error("type mismatch: BOOLEAN + INTEGER")),
+        ]);
+    }
+
+    #[test]
+    fn eval_builtins() {
+        test_eval_all(&[
+            (r#"len("");"#, Object::Integer(0)),
+            (r#"len("four");"#, Object::Integer(4)),
+            (r#"len("Здравствуйте");"#, Object::Integer(12)),
+            ("len([1, 2, 3]);", Object::Integer(3)),
+            ("len({1: 2});", Object::Integer(1)),
+            ("len(1);", error("argument to `len` not supported, got INTEGER")),
+            (r#"len("one", "two");"#, error("wrong number of arguments: expected 1, got 2")),
+            ("first([1, 2]);", Object::Integer(1)),
+            ("first([]);", Object::Null),
+            ("last([1, 2]);", Object::Integer(2)),
+            ("last(1);", error("argument to `last` not supported, got INTEGER")),
+            ("rest([1, 2, 3]);", Object::Array(vec![Object::Integer(2), Object::Integer(3)])),
+            ("rest([1]);", Object::Array(vec![])),
+            ("rest([]);", Object::Null),
+            ("let a = [1]; push(a, 2); a;", Object::Array(vec![Object::Integer(1)])),
+            ("push([1], 2);", Object::Array(vec![Object::Integer(1), Object::Integer(2)])),
+            ("push(1, 2);", error("argument to `push` not supported, got INTEGER")),
+            ("puts(1, \"a\");", Object::Null),
+            ("type_of(1.5);", Object::String("FLOAT".into())),
+            ("type_of(len);", Object::String("BUILTIN".into())),
+            ("str(1) + str([true]);", Object::String("1[true]".into())),
+            (r#"int(" -42 ") + int(2.9) + int(true);"#, Object::Integer(-39)),
+            (r#"int("4x");"#, error(r#"cannot convert "4x" to INTEGER"#)),
+            ("int(1e300);", error("cannot convert 1e300 to INTEGER")),
+            ("int([]);", error("argument to `int` not supported, got ARRAY")),
+            ("let len = fn(x) { 5 }; len([]);", Object::Integer(5)),
+        ]);
+    }
+
+    #[test]
+    fn eval_if_else() {
+        test_eval_all(&[
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ]);
+    }
+
+    #[test]
+    fn eval_return() {
+        test_eval_all(&[
+            ("return 10; 9;", Object::Integer(10)),
+            ("9; return 2 * 5; 9;", Object::Integer(10)),
+            (
+                "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+                Object::Integer(10),
+            ),
+            (
+                "let f = fn(x) { if (x) { return 1; } 2 }; f(true) + f(false);",
+                Object::Integer(3),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn eval_let() {
+        test_eval_all(&[
+            ("let a = 5; a;", Object::Integer(5)),
+            ("let a = 5 * 5; a;", Object::Integer(25)),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", Object::Integer(15)),
+            ("let a = 5;", Object::Null),
+        ]);
+    }
+
+    #[test]
+    fn eval_functions() {
+        test_eval_all(&[
+            ("let identity = fn(x) { x; }; identity(5);", Object::Integer(5)),
+            ("let identity = fn(x) { return x; }; identity(5);", Object::Integer(5)),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", Object::Integer(20)),
+            ("fn(x) { x; }(5);", Object::Integer(5)),
+            ("let f = fn() {}; f();", Object::Null),
+            (
+                "let new_adder = fn(x) { fn(y) { x + y } }; let add_two = new_adder(2); add_two(3);",
+                Object::Integer(5),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn eval_closures() {
+        test_eval_all(&[
+            (
+                "let counter = fn() { let n = 0; fn() { n += 1; n } };
+                 let a = counter(); let b = counter(); a(); a(); b();
+                 [a(), b()];",
+                Object::Array(vec![Object::Integer(3), Object::Integer(2)]),
+            ),
+            (
+                "let curry = fn(f) { fn(x) { fn(y) { f(x, y) } } };
+                 let add = curry(fn(a, b) { a + b });
+                 let inc = add(1);
+                 inc(41) + add(2)(3);",
+                Object::Integer(47),
+            ),
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(20);",
+                Object::Integer(6765),
+            ),
+            (
+                "let reduce = fn(arr, acc, f) {
+                   if (len(arr) == 0) { acc } else { reduce(rest(arr), f(acc, first(arr)), f) }
+                 };
+                 reduce([1, 2, 3, 4], 0, fn(acc, x) { acc + x * x });",
+                Object::Integer(30),
+            ),
+            (
+                "let x = 1; let f = fn(x) { let x = x * 10; fn() { x } }; let g = f(2); let x = 5; g() + x;",
+                Object::Integer(25),
+            ),
+            ("let x = 1; let f = fn() { let x = 2; x }; f() * 10 + x;", Object::Integer(21)),
+        ]);
+    }
+
+    #[test]
+    fn eval_function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function(function) => {
+                assert_eq!(function.params, &["x"]);
+                assert_eq!(function.body.to_string(), "{ (x + 2); }");
+            }
+            other => panic!("expected function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn eval_errors() {
+        test_eval_all(&[
+            ("5 + true;", error("type mismatch: INTEGER + BOOLEAN")),
+            ("5 + true; 5;", error("type mismatch: INTEGER + BOOLEAN")),
+            ("-true;", error("unknown operator: -BOOLEAN")),
+            ("true + false;", error("unknown operator: BOOLEAN + BOOLEAN")),
+            ("5; true + false; 5;", error("unknown operator: BOOLEAN + BOOLEAN")),
+            ("if (10 > 1) { true + false; }", error("unknown operator: BOOLEAN + BOOLEAN")),
+            ("foobar;", error("identifier not found: foobar")),
+            ("5 / 0;", error("division by zero")),
+            ("9223372036854775807 + 1;", error("integer overflow")),
+            ("5(1);", error("not a function: INTEGER")),
+            ("fn(x) { x }();", error("wrong number of arguments: expected 1, got 0")),
+            ("let f = fn(x) { y }; f(1);", error("identifier not found: y")),
+            ("let x = -true; 5;", error("unknown operator: -BOOLEAN")),
+        ]);
+    }
+
+    #[test]
+    fn eval_unbounded_recursion() {
+        // on a thread of the default size the stack runs out long before the call depth does
+        let recursion = std::thread::spawn(|| {
+            test_eval_all(&[
+                ("let f = fn(x) { f(x + 1) }; f(0);", error(STACK_OVERFLOW)),
+                ("let f = fn(n) { 1 + f(n + 1) }; f(0);", error(STACK_OVERFLOW)),
+                ("let f = fn(n) { [[[[[[[[[[f(n)]]]]]]]]]] }; f(0);", error(STACK_OVERFLOW)),
+            ]);
+        });
+        recursion.join().unwrap();
+    }
+
+    #[test]
+    fn eval_deep_recursion_on_a_large_stack() {
+        let recursion = std::thread::Builder::new().stack_size(STACK_SIZE).spawn(|| {
+            let eval = |input: &str| {
+                Evaluator::new()
+                    .with_stack_size(STACK_SIZE)
+                    .eval_program(&parse(input).unwrap(), &Environment::new())
+            };
+            let count = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };";
+            assert_eq!(eval(&format!("{} f(9999);", count)), Object::Integer(9999));
+            assert_eq!(eval(&format!("{} f(10000);", count)), error(STACK_OVERFLOW));
+        });
+        recursion.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn eval_error_span() {
+        let input = "let f = fn(x) {\n  x + true;\n};\nf(1);";
+        let program = parse(input).unwrap();
+        let mut evaluator = Evaluator::new();
+        let res = evaluator.eval_program(&program, &Environment::new());
+        assert_eq!(res, error("type mismatch: INTEGER + BOOLEAN"));
+        let span = evaluator.error_span().unwrap();
+        assert_eq!(&input[span.as_range()], "x + true;");
+    }
+
+    #[test]
+    fn eval_error_statement() {
+        let (program, _) = crate::parser::parse_with_errors("let = 5;");
+        assert!(eval(&program).is_error());
+    }
+}
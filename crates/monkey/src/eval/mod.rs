@@ -0,0 +1,14 @@
+mod builtins;
+mod environment;
+mod evaluator;
+mod native;
+mod object;
+mod runtime_error;
+
+pub use builtins::{Builtin, BUILTINS};
+pub use environment::{Env, Environment};
+pub use evaluator::{eval, Evaluator, DEFAULT_STACK_SIZE, MAX_CALL_DEPTH, STACK_SIZE};
+pub(crate) use evaluator::{build_hash, eval_index, eval_infix, eval_prefix};
+pub use native::{FromObject, NativeFunction, TypedFunction};
+pub use object::{Function, HashKey, Object};
+pub use runtime_error::RuntimeError;
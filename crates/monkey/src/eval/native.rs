@@ -0,0 +1,211 @@
+use std::fmt;
+
+use super::{HashKey, Object, RuntimeError};
+
+type NativeFn = dyn Fn(&[Object]) -> Result<Object, RuntimeError>;
+
+/// A function provided by the host application, see `Interpreter::register_fn`
+pub struct NativeFunction {
+    pub name: String,
+    func: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: impl Into<String>,
+        func: impl Fn(&[Object]) -> Result<Object, RuntimeError> + 'static,
+    ) -> NativeFunction {
+        NativeFunction {
+            name: name.into(),
+            func: Box::new(func),
+        }
+    }
+
+    /// Wraps a function with typed arguments, which are converted with `FromObject`
+    pub fn typed<Args>(name: impl Into<String>, func: impl TypedFunction<Args> + 'static) -> Self {
+        let name = name.into();
+        let fn_name = name.clone();
+        NativeFunction::new(name, move |args: &[Object]| {
+            if args.len() != func.arity() {
+                return Err(RuntimeError::new(format!(
+                    "wrong number of arguments: expected {}, got {}",
+                    func.arity(),
+                    args.len()
+                )));
+            }
+            func.call(&fn_name, args)
+        })
+    }
+
+    pub fn call(&self, args: &[Object]) -> Result<Object, RuntimeError> {
+        (self.func)(args)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl PartialEq for NativeFunction {
+    /// Native functions are only equal to themselves
+    fn eq(&self, other: &NativeFunction) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "native function {}", self.name)
+    }
+}
+
+/// Rust types that Monkey values can be converted to, for the arguments of typed native functions
+pub trait FromObject: Sized {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError>;
+}
+
+fn expected(type_name: &str, obj: &Object) -> RuntimeError {
+    RuntimeError::new(format!("expected {}, got {}", type_name, obj.type_name()))
+}
+
+impl FromObject for Object {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError> {
+        Ok(obj.clone())
+    }
+}
+
+impl FromObject for i64 {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError> {
+        match obj {
+            Object::Integer(x) => Ok(*x),
+            _ => Err(expected("INTEGER", obj)),
+        }
+    }
+}
+
+/// Integers are accepted too, like in arithmetic
+impl FromObject for f64 {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError> {
+        match obj {
+            Object::Float(x) => Ok(*x),
+            Object::Integer(x) => Ok(*x as f64),
+            _ => Err(expected("FLOAT", obj)),
+        }
+    }
+}
+
+impl FromObject for bool {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError> {
+        match obj {
+            Object::Boolean(x) => Ok(*x),
+            _ => Err(expected("BOOLEAN", obj)),
+        }
+    }
+}
+
+impl FromObject for String {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError> {
+        match obj {
+            Object::String(x) => Ok(x.clone()),
+            _ => Err(expected("STRING", obj)),
+        }
+    }
+}
+
+impl<T: FromObject> FromObject for Vec<T> {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError> {
+        match obj {
+            Object::Array(items) => items.iter().map(T::from_object).collect(),
+            _ => Err(expected("ARRAY", obj)),
+        }
+    }
+}
+
+impl FromObject for HashKey {
+    fn from_object(obj: &Object) -> Result<Self, RuntimeError> {
+        HashKey::from_object(obj).ok_or_else(|| expected("hash key", obj))
+    }
+}
+
+/// Functions whose arguments implement `FromObject` and that return something that converts into
+/// an `Object`. Implemented for closures with up to four arguments.
+pub trait TypedFunction<Args> {
+    fn arity(&self) -> usize;
+
+    /// Converts the arguments and calls the function. `args` has exactly `arity` elements, `name`
+    /// is only used in error messages.
+    fn call(&self, name: &str, args: &[Object]) -> Result<Object, RuntimeError>;
+}
+
+macro_rules! typed_function {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> TypedFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Result<R, RuntimeError>,
+            R: Into<Object>,
+            $($arg: FromObject),*
+        {
+            fn arity(&self) -> usize {
+                <[&str]>::len(&[$(stringify!($arg)),*])
+            }
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn call(&self, name: &str, args: &[Object]) -> Result<Object, RuntimeError> {
+                let mut args = args.iter().enumerate();
+                $(
+                    let (idx, arg) = args.next().expect("the arity was checked");
+                    let $arg = $arg::from_object(arg).map_err(|err| {
+                        let message = format!("argument {} to `{}`: {}", idx + 1, name, err.message);
+                        RuntimeError::new(message)
+                    })?;
+                )*
+                self($($arg),*).map(Into::into)
+            }
+        }
+    };
+}
+
+typed_function!();
+typed_function!(A);
+typed_function!(A, B);
+typed_function!(A, B, C);
+typed_function!(A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_arguments() {
+        let add = NativeFunction::typed("add", |a: i64, b: f64| Ok(a as f64 + b));
+        assert_eq!(add.call(&[1.into(), 2.into()]), Ok(Object::Float(3.0)));
+        assert_eq!(
+            add.call(&[1.into()]),
+            Err(RuntimeError::new("wrong number of arguments: expected 2, got 1"))
+        );
+        assert_eq!(
+            add.call(&[1.into(), "2".into()]),
+            Err(RuntimeError::new("argument 2 to `add`: expected FLOAT, got STRING"))
+        );
+
+        let join = NativeFunction::typed("join", |items: Vec<String>, sep: String| {
+            Ok(items.join(&sep))
+        });
+        let items = Object::from(vec!["a", "b"]);
+        assert_eq!(
+            join.call(&[items, ",".into()]),
+            Ok(Object::String("a,b".into()))
+        );
+        assert_eq!(
+            join.call(&[vec![Object::Integer(1)].into(), ",".into()]),
+            Err(RuntimeError::new("argument 1 to `join`: expected STRING, got INTEGER"))
+        );
+
+        let nothing = NativeFunction::typed("nothing", || Ok(()));
+        assert_eq!(nothing.call(&[]), Ok(Object::Null));
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::BlockStatement;
+use crate::common::FileId;
+use crate::lexer;
+use crate::vm::Closure;
+
+use super::{Builtin, Env, NativeFunction};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Object>),
+    /// keys are kept sorted, so that printing a hash always gives the same output
+    Hash(BTreeMap<HashKey, Object>),
+    Null,
+    /// Wraps the value of a `return` statement while it unwinds to the enclosing function
+    ReturnValue(Box<Object>),
+    Error(String),
+    Function(Rc<Function>),
+    Builtin(&'static Builtin),
+    Native(Rc<NativeFunction>),
+    /// a function compiled for the virtual machine
+    Closure(Rc<Closure>),
+}
+
+impl Object {
+    /// The name of the type, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(_) => "ERROR",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Native(_) => "NATIVE_FUNCTION",
+            Object::Closure(_) => "FUNCTION",
+        }
+    }
+
+    /// Everything except `false` and `null` is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Boolean(false) | Object::Null)
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    /// Formats the object as an element of an array or hash, where strings are quoted
+    fn fmt_element(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::String(x) => write!(f, "\"{}\"", lexer::escape(x)),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(x) => write!(f, "{}", x),
+            Object::Float(x) => write!(f, "{:?}", x),
+            Object::Boolean(x) => write!(f, "{}", x),
+            Object::String(x) => write!(f, "{}", x),
+            Object::Array(items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_element(f)?;
+                }
+                write!(f, "]")
+            }
+            Object::Hash(pairs) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in pairs.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    Object::from(key.clone()).fmt_element(f)?;
+                    write!(f, ": ")?;
+                    value.fmt_element(f)?;
+                }
+                write!(f, "}}")
+            }
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(x) => write!(f, "{}", x),
+            Object::Error(message) => write!(f, "ERROR: {}", message),
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Builtin(builtin) => write!(f, "{}", builtin),
+            Object::Native(native) => write!(f, "{}", native),
+            Object::Closure(closure) => write!(f, "{}", closure),
+        }
+    }
+}
+
+impl From<()> for Object {
+    fn from(_: ()) -> Object {
+        Object::Null
+    }
+}
+
+impl From<i64> for Object {
+    fn from(x: i64) -> Object {
+        Object::Integer(x)
+    }
+}
+
+impl From<f64> for Object {
+    fn from(x: f64) -> Object {
+        Object::Float(x)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(x: bool) -> Object {
+        Object::Boolean(x)
+    }
+}
+
+impl From<String> for Object {
+    fn from(x: String) -> Object {
+        Object::String(x)
+    }
+}
+
+impl From<&str> for Object {
+    fn from(x: &str) -> Object {
+        Object::String(x.to_string())
+    }
+}
+
+impl<T: Into<Object>> From<Vec<T>> for Object {
+    fn from(items: Vec<T>) -> Object {
+        Object::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// The objects that can be used as keys of a hash
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl HashKey {
+    /// Converts the object to a key, or returns `None` if the type cannot be used as one
+    pub fn from_object(obj: &Object) -> Option<HashKey> {
+        match obj {
+            Object::Integer(x) => Some(HashKey::Integer(*x)),
+            Object::Boolean(x) => Some(HashKey::Boolean(*x)),
+            Object::String(x) => Some(HashKey::String(x.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Object {
+        match key {
+            HashKey::Integer(x) => Object::Integer(x),
+            HashKey::Boolean(x) => Object::Boolean(x),
+            HashKey::String(x) => Object::String(x),
+        }
+    }
+}
+
+/// A function value together with the environment it was defined in
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: BlockStatement,
+    pub env: Env,
+    /// the file the body was parsed from, if the program was evaluated with
+    /// `Evaluator::eval_program_in`
+    pub file: Option<FileId>,
+}
+
+impl fmt::Debug for Function {
+    // the environment is left out because it can contain the function itself
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+impl PartialEq for Function {
+    /// Functions are only equal to themselves
+    fn eq(&self, other: &Function) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fn({}) {}", self.params.join(", "), self.body)
+    }
+}
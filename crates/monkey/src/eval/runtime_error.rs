@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+/// An error raised by a native function. It becomes a Monkey runtime error at the statement that
+/// made the call.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{message}")]
+pub struct RuntimeError {
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> RuntimeError {
+        RuntimeError {
+            message: message.into(),
+        }
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> RuntimeError {
+        RuntimeError::new(message)
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> RuntimeError {
+        RuntimeError::new(message)
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::ast::Program;
+use crate::common::{FileId, Span, Spanned};
+use crate::eval::{Env, Environment, Evaluator, NativeFunction, Object, RuntimeError, TypedFunction};
+use crate::parser::{self, ParseError};
+
+#[derive(Error, Debug)]
+pub enum InterpreterError {
+    #[error("Could not read `{}`: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// every error in the script, in source order
+    #[error("{}", first.node)]
+    Parse {
+        first: Spanned<ParseError>,
+        rest: Vec<Spanned<ParseError>>,
+    },
+
+    /// `span` is the statement that failed, in the source it was parsed from. That is an earlier
+    /// script if the error happened in a function defined there. `file` is that source if it was
+    /// evaluated with `eval_program_in`.
+    #[error("{message}")]
+    Runtime {
+        message: String,
+        span: Option<Span>,
+        file: Option<FileId>,
+    },
+}
+
+/// Evaluates scripts for a host application. Globals and functions defined by one script are
+/// visible to the scripts evaluated after it. Runaway recursion in a script is an error, the
+/// interpreter stops before it uses half of a stack of `eval::DEFAULT_STACK_SIZE` bytes. Hosts
+/// that run it on a larger stack can allow deeper recursion with `with_stack_size`.
+#[derive(Debug)]
+pub struct Interpreter {
+    env: Env,
+    evaluator: Evaluator,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter {
+            env: Environment::new(),
+            evaluator: Evaluator::new(),
+        }
+    }
+
+    /// Lets scripts recurse deeper when the interpreter runs on a thread whose stack is `bytes`
+    /// large, see `Evaluator::with_stack_size`
+    pub fn with_stack_size(mut self, bytes: usize) -> Interpreter {
+        self.evaluator = self.evaluator.with_stack_size(bytes);
+        self
+    }
+
+    /// Parses and evaluates the source, returning the value of the last statement
+    pub fn eval_str(&mut self, source: &str) -> Result<Object, InterpreterError> {
+        let (program, errors) = parser::parse_with_errors(source);
+        let mut errors = errors.into_iter();
+        if let Some(first) = errors.next() {
+            return Err(InterpreterError::Parse {
+                first,
+                rest: errors.collect(),
+            });
+        }
+        self.eval_program(&program)
+    }
+
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Object, InterpreterError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| InterpreterError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        self.eval_str(&source)
+    }
+
+    /// Evaluates a program that has already been parsed
+    pub fn eval_program(&mut self, program: &Program) -> Result<Object, InterpreterError> {
+        let value = self.evaluator.eval_program(program, &self.env);
+        self.check(value)
+    }
+
+    /// Evaluates a program parsed from a file of a `SourceMap`, so that runtime errors say which
+    /// file they point into. Functions remember the file they were defined in.
+    pub fn eval_program_in(
+        &mut self,
+        file: FileId,
+        program: &Program,
+    ) -> Result<Object, InterpreterError> {
+        let value = self.evaluator.eval_program_in(file, program, &self.env);
+        self.check(value)
+    }
+
+    /// Binds a global, replacing any binding of the same name
+    pub fn set_global(&mut self, name: impl Into<String>, value: impl Into<Object>) {
+        self.env.borrow_mut().set(name, value.into());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        self.env.borrow().get(name)
+    }
+
+    /// Binds a global to a Rust function. An error it returns becomes a runtime error at the
+    /// statement that called it.
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        func: impl Fn(&[Object]) -> Result<Object, RuntimeError> + 'static,
+    ) {
+        self.register(NativeFunction::new(name, func));
+    }
+
+    /// Like `register_fn`, for a closure with typed arguments like `|x: i64, name: String|`.
+    /// Arguments of the wrong type are runtime errors.
+    pub fn register_typed_fn<Args>(
+        &mut self,
+        name: &str,
+        func: impl TypedFunction<Args> + 'static,
+    ) {
+        self.register(NativeFunction::typed(name, func));
+    }
+
+    fn register(&mut self, native: NativeFunction) {
+        let name = native.name.clone();
+        self.set_global(name, Object::Native(Rc::new(native)));
+    }
+
+    /// Calls the global function, or builtin, with the name
+    pub fn call_function(
+        &mut self,
+        name: &str,
+        args: Vec<Object>,
+    ) -> Result<Object, InterpreterError> {
+        let function = match self.evaluator.eval_identifier(name, &self.env) {
+            Object::Error(message) => {
+                return Err(InterpreterError::Runtime {
+                    message,
+                    span: None,
+                    file: None,
+                })
+            }
+            function => function,
+        };
+        let value = self.evaluator.call(function, args);
+        self.check(value)
+    }
+
+    fn check(&self, value: Object) -> Result<Object, InterpreterError> {
+        match value {
+            Object::Error(message) => Err(InterpreterError::Runtime {
+                message,
+                span: self.evaluator.error_span(),
+                file: self.evaluator.error_file(),
+            }),
+            value => Ok(value),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::common::{Accept, AdvancedIter, Peekable};
+use crate::common::{AdvancedIter, Peekable};
 use std::iter::FusedIterator;
 use std::str::CharIndices;
 
@@ -55,6 +55,10 @@ impl<'a> AdvancedChars<'a> {
     pub fn len(&self) -> usize {
         self.length
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
 }
 
 impl<'a> Peekable for AdvancedChars<'a> {
@@ -72,23 +76,13 @@ impl<'a> Iterator for AdvancedChars<'a> {
         let (current_pos, current_ch) = self.chars.next().unzip();
         self.current_pos = current_pos;
         self.current_ch = current_ch;
-        let (peek_pos, peek_ch) = self.chars.peek().map(Clone::clone).unzip();
+        let (peek_pos, peek_ch) = self.chars.peek().copied().unzip();
         self.peek_pos = peek_pos;
         self.peek_ch = peek_ch;
         current_ch
     }
 }
 
-trait OptionExt<T, U> {
-    fn unzip(self) -> (Option<T>, Option<U>);
-}
-
-impl<T, U> OptionExt<T, U> for Option<(T, U)> {
-    fn unzip(self) -> (Option<T>, Option<U>) {
-        self.map_or((None, None), |(t, u)| (Some(t), Some(u)))
-    }
-}
-
 impl<'a> FusedIterator for AdvancedChars<'a> {}
 
 #[cfg(test)]
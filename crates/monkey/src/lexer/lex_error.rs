@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+/// Problems the lexer finds on its own. They are reported through the parser together with the
+/// parse errors.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LexError {
+    #[error("Unterminated block comment")]
+    UnterminatedComment,
+}
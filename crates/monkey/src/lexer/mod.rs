@@ -2,6 +2,9 @@
 #[allow(unused_variables)]
 mod tokens;
 mod advanced_chars;
+mod lex_error;
+mod number;
+mod unescape;
 
 use std::str;
 use std::time::Duration;
@@ -10,9 +13,12 @@ use std::sync::mpsc::{Sender, Receiver, channel, RecvTimeoutError};
 use log::debug;
 use log::info;
 
-use crate::common::{AdvancedIter, Accept, Peekable};
-use advanced_chars::AdvancedChars;
+use crate::common::{Accept, Peekable, Span, Spanned};
+pub use advanced_chars::AdvancedChars;
+pub use lex_error::LexError;
+pub use number::{parse_float, parse_int, NumberError};
 pub use tokens::Token;
+pub use unescape::{escape, unescape, EscapeError};
 use Token::*;
 
 /// lexer struct, holds input str, chars iterator, and start position which is the memorized
@@ -22,6 +28,7 @@ pub struct Lexer<'input> {
     input: &'input str,
     chars: AdvancedChars<'input>,
     start: usize,
+    lossless: bool,
 }
 
 impl<'input> Lexer<'input> {
@@ -32,130 +39,290 @@ impl<'input> Lexer<'input> {
             input,
             chars,
             start: 0,
+            lossless: false,
         }
     }
 
+    /// create a lexer that also emits whitespace and comments as trivia tokens, so that the text
+    /// of all tokens put together is the input
+    pub fn lossless(input: &str) -> Lexer<'_> {
+        Lexer {
+            lossless: true,
+            ..Lexer::new(input)
+        }
+    }
+
+    /// the input the lexer was created from
+    pub fn input(&self) -> &'input str {
+        self.input
+    }
+
     /// moves start back to peek position
     fn ignore(&mut self) {
-        let new_start = self.chars.peek_pos_or_end(); 
+        let new_start = self.chars.peek_pos_or_end();
         debug!("ignored: start to {}", new_start);
         self.start = new_start;
     }
 
+    /// wraps the token with the span from start to the peek position and moves start past it
+    fn emit(&mut self, token: Token<'input>) -> Spanned<Token<'input>> {
+        let span = Span::new(self.start, self.chars.peek_pos_or_end());
+        debug!("emitted {:?} at {}", token, span);
+        self.ignore();
+        Spanned::new(token, span)
+    }
+
     /// the main lexer funciton that determines what the token is and weather the state should be
-    /// passed on to a new function
-    fn lex_main(&mut self) -> Option<Token<'input>> {
+    /// passed on to a new function. Unless the lexer is lossless, trivia is skipped here in a
+    /// loop, so long runs of comments do not nest calls.
+    fn lex_main(&mut self) -> Option<Spanned<Token<'input>>> {
+        loop {
+            let res = self.lex_token()?;
+            if self.lossless || !res.node.is_trivia() {
+                return Some(res);
+            }
+        }
+    }
+
+    /// lexes the next token, including trivia
+    fn lex_token(&mut self) -> Option<Spanned<Token<'input>>> {
         let c = self.chars.next()?;
         // if the match arm returns a token, that means the token can only be one char long. if
         // there is ambiguity about which token should be returned or weather the token is multiple
         // chars long, a new state function is called that will determine the token
         let res = match c {
             '=' => self.assign_or_eq(),
-            ';' => Some(Semicolon),
-            '(' => Some(Lparen),
-            ')' => Some(Rparen),
-            ',' => Some(Comma),
-            '+' => Some(Plus),
-            '*' => Some(Asterisk),
+            ';' => Some(self.emit(Semicolon)),
+            ':' => Some(self.emit(Colon)),
+            '(' => Some(self.emit(Lparen)),
+            ')' => Some(self.emit(Rparen)),
+            ',' => Some(self.emit(Comma)),
+            '+' => Some(self.operator_or_assign(Plus, PlusAssign)),
+            '*' => Some(self.operator_or_assign(Asterisk, AsteriskAssign)),
             '/' => self.slash_or_comment(),
-            '-' => Some(Minus),
-            '{' => Some(Lbrace),
-            '}' => Some(Rbrace),
+            '-' => Some(self.operator_or_assign(Minus, MinusAssign)),
+            '%' => Some(self.operator_or_assign(Percent, PercentAssign)),
+            '&' => Some(self.double('&', And)),
+            '|' => Some(self.double('|', Or)),
+            '{' => Some(self.emit(Lbrace)),
+            '}' => Some(self.emit(Rbrace)),
+            '[' => Some(self.emit(Lbracket)),
+            ']' => Some(self.emit(Rbracket)),
             '>' => self.gt(),
             '<' => self.lt(),
             '!' => self.bang_or_not_eq(),
-            _ if is_start_of_number(&c) => self.number(),
+            '"' => self.string(),
+            _ if is_digit(&c) => self.number(c),
             _ if is_letter(&c) => self.keyword(),
             _ if is_whitespace(&c) => self.whitespace(),
-            _ => Some(Illegal),
+            _ => {
+                let slice = self.current_slice();
+                Some(self.emit(Illegal(slice)))
+            }
         };
         debug!("res: {:?}", res);
-        self.ignore();
         res
     }
 
-    fn whitespace(&mut self) -> Option<Token<'input>> {
-        self.ignore();
-        self.lex_main()
+    fn whitespace(&mut self) -> Option<Spanned<Token<'input>>> {
+        self.chars.accept_while(is_whitespace);
+        let slice = self.current_slice();
+        Some(self.emit(Whitespace(slice)))
     }
 
-    fn slash_or_comment(&mut self) -> Option<Token<'input>> {
+    fn slash_or_comment(&mut self) -> Option<Spanned<Token<'input>>> {
         if self.chars.accept('/') {
-            self.comment()
+            // `///` starts a doc comment, but `////` is a normal comment again
+            if self.rest().starts_with('/') && !self.rest().starts_with("//") {
+                self.doc_comment()
+            } else {
+                self.comment()
+            }
+        } else if self.chars.accept('*') {
+            self.block_comment()
         } else {
-            Some(Slash)
+            Some(self.operator_or_assign(Slash, SlashAssign))
         }
     }
 
-    fn comment(&mut self) -> Option<Token<'input>> {
+    /// Emits `assign` if the operator is followed by `=`, like `+=`
+    fn operator_or_assign(
+        &mut self,
+        operator: Token<'input>,
+        assign: Token<'input>,
+    ) -> Spanned<Token<'input>> {
+        if self.chars.accept('=') {
+            self.emit(assign)
+        } else {
+            self.emit(operator)
+        }
+    }
+
+    /// Emits `token` if the char is doubled, like `&&`. A single one is illegal.
+    fn double(&mut self, c: char, token: Token<'input>) -> Spanned<Token<'input>> {
+        if self.chars.accept(c) {
+            self.emit(token)
+        } else {
+            let slice = self.current_slice();
+            self.emit(Illegal(slice))
+        }
+    }
+
+    fn comment(&mut self) -> Option<Spanned<Token<'input>>> {
         info!("In comment state");
-        if self.chars.find(is_linebreak).is_none() && self.input.lines().count() != 1 {
-            panic!("Could not find char that matched function is_linebreak");
-        };
-        self.ignore();
-        self.lex_main()
+        // a comment without a line break runs until the end of the input, the line break itself
+        // is whitespace
+        self.chars.accept_while(|c| !is_linebreak(c));
+        let slice = self.current_slice();
+        Some(self.emit(Comment(slice)))
+    }
+
+    fn doc_comment(&mut self) -> Option<Spanned<Token<'input>>> {
+        info!("In doc comment state");
+        self.chars.accept_while(|c| !is_linebreak(c));
+        let slice = self.current_slice();
+        Some(self.emit(DocComment(slice)))
+    }
+
+    /// Lexes a `/* */` comment. Block comments nest, so every `/*` inside needs its own `*/`.
+    fn block_comment(&mut self) -> Option<Spanned<Token<'input>>> {
+        info!("In block comment state");
+        let mut depth = 1;
+        while depth > 0 {
+            match self.chars.next() {
+                Some('/') if self.chars.accept('*') => depth += 1,
+                Some('*') if self.chars.accept('/') => depth -= 1,
+                Some(_) => (),
+                None => {
+                    let slice = self.current_slice();
+                    return Some(self.emit(UnterminatedComment(slice)));
+                }
+            }
+        }
+        let slice = self.current_slice();
+        Some(self.emit(BlockComment(slice)))
     }
 
-    fn assign_or_eq(&mut self) -> Option<Token<'input>> {
+    fn assign_or_eq(&mut self) -> Option<Spanned<Token<'input>>> {
         if self.chars.accept('=') {
-            Some(Eq)
+            Some(self.emit(Eq))
         } else {
-            Some(Assign)
+            Some(self.emit(Assign))
         }
     }
 
-    fn gt(&mut self) -> Option<Token<'input>> {
+    fn gt(&mut self) -> Option<Spanned<Token<'input>>> {
         if self.chars.accept('=') {
-            Some(GtEq)
+            Some(self.emit(GtEq))
         } else {
-            Some(Gt)
+            Some(self.emit(Gt))
         }
     }
 
-    fn bang_or_not_eq(&mut self) -> Option<Token<'input>> {
+    fn bang_or_not_eq(&mut self) -> Option<Spanned<Token<'input>>> {
         if self.chars.accept('=') {
-            Some(NotEq)
+            Some(self.emit(NotEq))
         } else {
-            Some(Bang)
+            Some(self.emit(Bang))
         }
     }
 
-    fn lt(&mut self) -> Option<Token<'input>> {
+    fn lt(&mut self) -> Option<Spanned<Token<'input>>> {
         if self.chars.accept('=') {
-            Some(LtEq)
+            Some(self.emit(LtEq))
         } else {
-            Some(Lt)
+            Some(self.emit(Lt))
         }
     }
 
-    fn number(&mut self) -> Option<Token<'input>> {
+    /// Runs until the closing quote or the end of the input. Escapes are only skipped over here so
+    /// that an escaped quote does not end the string, they are decoded by the parser.
+    fn string(&mut self) -> Option<Spanned<Token<'input>>> {
+        info!("in string state");
+        while let Some(c) = self.chars.next() {
+            match c {
+                '"' => break,
+                '\\' => {
+                    self.chars.next();
+                }
+                _ => (),
+            }
+        }
+        let slice = self.current_slice();
+        Some(self.emit(Str(slice)))
+    }
+
+    /// Lexes integers with an optional `0x`, `0o` or `0b` prefix and decimal floats with an
+    /// optional exponent. Digits are only checked against the radix by the parser, and trailing
+    /// letters are made part of the literal so that the parser can report them.
+    fn number(&mut self, first: char) -> Option<Spanned<Token<'input>>> {
         info!("in number state");
-        self.chars.accept_while(is_digit);
-        if self.chars.accept('.') {
-            self.chars.accept_while(is_digit);
+        let prefixed = first == '0' && self.rest().starts_with(['x', 'X', 'o', 'O', 'b', 'B']);
+        if prefixed {
+            self.chars.next();
+            self.chars.accept_while(is_ident_char);
+            let slice = self.current_slice();
+            return Some(self.emit(Int(slice)));
         }
 
-        Some(Number(self.current_slice()))
+        self.chars.accept_while(is_digit_or_underscore);
+        let mut is_float = false;
+        // `1.` is not a float, so that `.` can be used for something else later
+        let fraction = self.rest().strip_prefix('.');
+        if fraction.is_some_and(|fraction| fraction.starts_with(|c: char| c.is_ascii_digit())) {
+            self.chars.next();
+            self.chars.accept_while(is_digit_or_underscore);
+            is_float = true;
+        }
+        if self.exponent_follows() {
+            self.chars.next();
+            if !self.chars.accept('+') {
+                self.chars.accept('-');
+            }
+            self.chars.accept_while(is_digit_or_underscore);
+            is_float = true;
+        }
+        self.chars.accept_while(is_ident_char);
+
+        let slice = self.current_slice();
+        Some(self.emit(if is_float { Float(slice) } else { Int(slice) }))
     }
 
-    fn keyword(&mut self) -> Option<Token<'input>> {
+    /// Checks for `e`, an optional sign and a digit
+    fn exponent_follows(&self) -> bool {
+        let rest = self.rest();
+        let Some(exponent) = rest.strip_prefix(['e', 'E']) else {
+            return false;
+        };
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        exponent.starts_with(|c: char| c.is_ascii_digit())
+    }
+
+    /// The input that has not been consumed yet
+    fn rest(&self) -> &'input str {
+        &self.input[self.chars.peek_pos_or_end()..]
+    }
+
+    fn keyword(&mut self) -> Option<Spanned<Token<'input>>> {
         info!("in keyword state");
         self.chars.accept_while(is_letter);
-        match self.current_slice() {
-            "fn" => Some(Function),
-            "return" => Some(Return),
-            "let" => Some(Let),
-            "if" => Some(If),
-            "else" => Some(Else),
-            "true" => Some(True),
-            "false" => Some(False),
-            _ => self.ident(),
-        }
+        let token = match self.current_slice() {
+            "fn" => Function,
+            "return" => Return,
+            "let" => Let,
+            "if" => If,
+            "else" => Else,
+            "true" => True,
+            "false" => False,
+            _ => return self.ident(),
+        };
+        Some(self.emit(token))
     }
 
-    fn ident(&mut self) -> Option<Token<'input>> {
+    fn ident(&mut self) -> Option<Spanned<Token<'input>>> {
         info!("in ident state");
-        Some(Ident(self.current_slice()))
+        let slice = self.current_slice();
+        Some(self.emit(Ident(slice)))
     }
 
     fn current_slice(&mut self) -> &'input str {
@@ -170,14 +337,14 @@ impl<'input> Lexer<'input> {
 }
 
 impl<'input> Iterator for Lexer<'input> {
-    type Item = Token<'input>;
+    type Item = Spanned<Token<'input>>;
 
-    fn next(&mut self) -> Option<Token<'input>> {
+    fn next(&mut self) -> Option<Spanned<Token<'input>>> {
         let res = self.lex_main();
         debug!("next token: {:?}", res);
         res
     }
-} 
+}
 
 // const fn is_linebreak(c: char) -> bool {
 //     c == '\n'
@@ -190,8 +357,13 @@ const fn is_whitespace(c: &char) -> bool {
     c.is_ascii_whitespace()
 }
 
-const fn is_start_of_number(c: &char) -> bool {
-    is_digit(c) || *c == '.'
+const fn is_digit_or_underscore(c: &char) -> bool {
+    is_digit(c) || *c == '_'
+}
+
+/// chars that can continue an identifier, or be trailing garbage of a number literal
+fn is_ident_char(c: &char) -> bool {
+    c.is_alphanumeric() || *c == '_'
 }
 
 /// checks if the char is an ascii digit
@@ -205,12 +377,12 @@ fn is_letter(c: &char) -> bool {
 }
 
 pub struct LexerSender<'input> {
-    sender: Sender<Option<Token<'input>>>,
+    sender: Sender<Option<Spanned<Token<'input>>>>,
     lexer: Lexer<'input>,
 }
 
 impl<'input> LexerSender<'input> {
-    fn new(input: &'input str, sender: Sender<Option<Token<'input>>>) -> LexerSender<'input> {
+    pub fn new(input: &'input str, sender: Sender<Option<Spanned<Token<'input>>>>) -> LexerSender<'input> {
         let lexer = Lexer::new(input);
         LexerSender {
             sender,
@@ -219,7 +391,7 @@ impl<'input> LexerSender<'input> {
     }
 
     /// Sends all the tokens and block
-    fn send(self) {
+    pub fn send(self) {
         for token in self.lexer {
             self.sender.send(Some(token)).expect("Failed to send token");
         }
@@ -228,12 +400,12 @@ impl<'input> LexerSender<'input> {
 }
 
 pub struct LexerReceiver<'input> {
-    receiver: Receiver<Option<Token<'input>>>,
+    receiver: Receiver<Option<Spanned<Token<'input>>>>,
     no_more: bool,
 }
 
 impl<'input> LexerReceiver<'input> {
-    fn new(receiver: Receiver<Option<Token<'input>>>) -> LexerReceiver<'input> {
+    pub fn new(receiver: Receiver<Option<Spanned<Token<'input>>>>) -> LexerReceiver<'input> {
         LexerReceiver {
             receiver,
             no_more: false,
@@ -242,9 +414,9 @@ impl<'input> LexerReceiver<'input> {
 }
 
 impl<'input> Iterator for LexerReceiver<'input> {
-    type Item = Result<Token<'input>, RecvTimeoutError>;
+    type Item = Result<Spanned<Token<'input>>, RecvTimeoutError>;
 
-    fn next(&mut self) -> Option<Result<Token<'input>, RecvTimeoutError>> {
+    fn next(&mut self) -> Option<Result<Spanned<Token<'input>>, RecvTimeoutError>> {
         if self.no_more {
             return None;
         }
@@ -259,59 +431,143 @@ impl<'input> Iterator for LexerReceiver<'input> {
     }
 }
 
-pub fn lexer_channel(input: &str) -> (LexerSender, LexerReceiver) {
+pub fn lexer_channel(input: &str) -> (LexerSender<'_>, LexerReceiver<'_>) {
     let (sender, receiver) = channel();
     let lexer_sender = LexerSender::new(input, sender);
     let lexer_receiver = LexerReceiver::new(receiver);
     (lexer_sender, lexer_receiver)
 }
 
+/// The token stream the parser reads. It is advanced one token like `AdvancedIter`, and moves doc
+/// comments and lexer errors aside so the grammar never sees them.
 pub struct AdvancedLexer<'input> {
-    lexer: AdvancedIter<Lexer<'input>>,
-    curr_token: Option<Token<'input>>,
+    lexer: Lexer<'input>,
+    peek_token: Option<Spanned<Token<'input>>>,
+    curr_token: Option<Spanned<Token<'input>>>,
+    input_len: usize,
+    docs: Vec<Spanned<&'input str>>,
+    errors: Vec<Spanned<LexError>>,
 }
 
 impl<'input> AdvancedLexer<'input> {
     pub fn new(input: &str) -> AdvancedLexer<'_> {
-        let lexer = AdvancedIter::new(Lexer::new(input));
-        let curr_token = None;
-        AdvancedLexer {
-            lexer,
-            curr_token,
+        let mut lexer = AdvancedLexer {
+            lexer: Lexer::new(input),
+            peek_token: None,
+            curr_token: None,
+            input_len: input.len(),
+            docs: Vec::new(),
+            errors: Vec::new(),
+        };
+        lexer.peek_token = lexer.next_significant();
+        lexer
+    }
+
+    fn next_significant(&mut self) -> Option<Spanned<Token<'input>>> {
+        loop {
+            let token = self.lexer.next()?;
+            match token.node {
+                DocComment(text) => self.docs.push(Spanned::new(text, token.span)),
+                _ if token.node.is_trivia() => (),
+                UnterminatedComment(_) => {
+                    // point at the opening `/*` instead of everything up to the end
+                    let span = Span::new(token.span.start, token.span.start + 2);
+                    self.errors
+                        .push(Spanned::new(LexError::UnterminatedComment, span));
+                }
+                _ => return Some(token),
+            }
         }
     }
 
-    pub fn curr_token(&self) -> Option<Token<'input>> {
+    pub fn curr_token(&self) -> Option<Spanned<Token<'input>>> {
         self.curr_token
     }
+
+    /// Takes the doc comments right before the peeked token, one line per comment with the `///`
+    /// and a single following space removed. Doc comments before earlier tokens are dropped.
+    pub fn take_docs(&mut self) -> Option<String> {
+        let after = self.curr_token.map_or(0, |token| token.span.end);
+        let lines: Vec<_> = self
+            .docs
+            .drain(..)
+            .filter(|doc| doc.span.start >= after)
+            .map(|doc| {
+                let text = &doc.node[3..];
+                text.strip_prefix(' ').unwrap_or(text).trim_end_matches('\r')
+            })
+            .collect();
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    /// Takes the errors the lexer found so far
+    pub fn take_errors(&mut self) -> Vec<Spanned<LexError>> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The span of the current token, or an empty span at the end of the input if there are no
+    /// more tokens
+    pub fn curr_span(&self) -> Span {
+        self.curr_token.map_or_else(|| self.end_span(), |token| token.span)
+    }
+
+    /// The span of the peeked token, or an empty span at the end of the input if there are no
+    /// more tokens
+    pub fn peek_span(&self) -> Span {
+        self.peek_token.map_or_else(|| self.end_span(), |token| token.span)
+    }
+
+    fn end_span(&self) -> Span {
+        let end = self.input_len;
+        Span::new(end, end)
+    }
 }
 
 impl<'input> Iterator for AdvancedLexer<'input> {
-    type Item = Token<'input>;
+    type Item = Spanned<Token<'input>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let curr_token = self.lexer.next();
+        let curr_token = self.peek_token.take();
         self.curr_token = curr_token;
+        if curr_token.is_some() {
+            self.peek_token = self.next_significant();
+        }
         curr_token
     }
 }
 
 impl<'input> Peekable for AdvancedLexer<'input> {
     fn peek(&self) -> Option<&Self::Item> {
-        self.lexer.peek()
+        self.peek_token.as_ref()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::ops::Range;
 
     fn test_lexer(input: &'static str, expected_tokens: &[Token]) {
         let _ = env_logger::builder().is_test(true).try_init();
-        let res: Vec<_> = Lexer::new(input).collect();
+        let res: Vec<_> = Lexer::new(input).map(|token| token.node).collect();
         assert_eq!(res, expected_tokens);
     }
 
+    fn test_spans(input: &'static str, expected_spans: &[(Token, Range<usize>)]) {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let res: Vec<_> = Lexer::new(input).collect();
+        let expected: Vec<_> = expected_spans
+            .iter()
+            .cloned()
+            .map(|(token, range)| Spanned::new(token, range.into()))
+            .collect();
+        assert_eq!(res, expected);
+    }
+
     #[test]
     fn lexer_init() {
         let mut lexer = Lexer::new("hello person");
@@ -371,7 +627,7 @@ mod tests {
     #[test]
     fn lex1_test() {
         let input = "let five = 5;";
-        let expected_tokens = &[Let, Ident("five"), Assign, Number("5"), Semicolon];
+        let expected_tokens = &[Let, Ident("five"), Assign, Int("5"), Semicolon];
         test_lexer(input, expected_tokens);
     }
 
@@ -409,11 +665,11 @@ mod tests {
             Let,
             Ident("add"),
             Assign,
-            Number("20"),
+            Int("20"),
             Semicolon,
-            Number("20"),
+            Int("20"),
             Eq,
-            Number("20"),
+            Int("20"),
             Semicolon,
         ];
 
@@ -424,7 +680,7 @@ mod tests {
     fn comment_and_other_test() {
         let input = "// this is a comment
 20 / 2;";
-        let expected_tokens = &[Number("20"), Slash, Number("2"), Semicolon];
+        let expected_tokens = &[Int("20"), Slash, Int("2"), Semicolon];
         test_lexer(input, expected_tokens);
     }
 
@@ -435,10 +691,119 @@ mod tests {
         test_lexer(input, expected_tokens);
     }
 
+    #[test]
+    fn block_comment_test() {
+        let input = "1 /* a /* nested */ still comment */ + /**/ 2 /* open /* */";
+        let expected_spans = &[
+            (Int("1"), 0..1),
+            (Plus, 37..38),
+            (Int("2"), 44..45),
+            (UnterminatedComment("/* open /* */"), 46..59),
+        ];
+        test_spans(input, expected_spans);
+    }
+
+    #[test]
+    fn doc_comment_test() {
+        let input = "/// doc\n//// not doc\n// not doc\n///\nlet";
+        let expected_spans = &[
+            (DocComment("/// doc"), 0..7),
+            (DocComment("///"), 32..35),
+            (Let, 36..39),
+        ];
+        test_spans(input, expected_spans);
+    }
+
+    #[test]
+    fn lossless_test() {
+        let input = "let x = 1; // one\n/* a /* b */ */\n/// doc\n  x";
+        let res: Vec<_> = Lexer::lossless(input).collect();
+        let expected: Vec<_> = [
+            (Let, 0..3),
+            (Whitespace(" "), 3..4),
+            (Ident("x"), 4..5),
+            (Whitespace(" "), 5..6),
+            (Assign, 6..7),
+            (Whitespace(" "), 7..8),
+            (Int("1"), 8..9),
+            (Semicolon, 9..10),
+            (Whitespace(" "), 10..11),
+            (Comment("// one"), 11..17),
+            (Whitespace("\n"), 17..18),
+            (BlockComment("/* a /* b */ */"), 18..33),
+            (Whitespace("\n"), 33..34),
+            (DocComment("/// doc"), 34..41),
+            (Whitespace("\n  "), 41..44),
+            (Ident("x"), 44..45),
+        ]
+        .iter()
+        .cloned()
+        .map(|(token, range)| Spanned::new(token, range.into()))
+        .collect();
+        assert_eq!(res, expected);
+    }
+
     #[test]
     fn operators_test() {
-        let input = "!-/*5;";
-        let expected_tokens = &[Bang, Minus, Slash, Asterisk, Number("5"), Semicolon];
+        // `/*` starts a block comment, so the slash and asterisk need a space between them
+        let input = "!-/ *5;";
+        let expected_tokens = &[Bang, Minus, Slash, Asterisk, Int("5"), Semicolon];
+        test_lexer(input, expected_tokens);
+    }
+
+    #[test]
+    fn collections_test() {
+        let input = "[1, 2][0]; {\"a\": 1}";
+        let expected_tokens = &[
+            Lbracket,
+            Int("1"),
+            Comma,
+            Int("2"),
+            Rbracket,
+            Lbracket,
+            Int("0"),
+            Rbracket,
+            Semicolon,
+            Lbrace,
+            Str("\"a\""),
+            Colon,
+            Int("1"),
+            Rbrace,
+        ];
+        test_lexer(input, expected_tokens);
+    }
+
+    #[test]
+    fn logical_and_assign_test() {
+        let input = "a && b || !c & d | e % 2; x = 1; x += 1 -= *= /= %= / =";
+        let expected_tokens = &[
+            Ident("a"),
+            And,
+            Ident("b"),
+            Or,
+            Bang,
+            Ident("c"),
+            Illegal("&"),
+            Ident("d"),
+            Illegal("|"),
+            Ident("e"),
+            Percent,
+            Int("2"),
+            Semicolon,
+            Ident("x"),
+            Assign,
+            Int("1"),
+            Semicolon,
+            Ident("x"),
+            PlusAssign,
+            Int("1"),
+            MinusAssign,
+            AsteriskAssign,
+            SlashAssign,
+            PercentAssign,
+            Slash,
+            Assign,
+        ];
         test_lexer(input, expected_tokens);
     }
 
@@ -460,9 +825,9 @@ return true;
         let expected_tokens = &[
             If,
             Lparen,
-            Number("5"),
+            Int("5"),
             Lt,
-            Number("10"),
+            Int("10"),
             Rparen,
             Lbrace,
             Return,
@@ -486,7 +851,7 @@ return true;
             Let,
             Ident("number"),
             Assign,
-            Number("50"),
+            Int("50"),
             Semicolon,
         ];
         test_lexer(input, expected_tokens);
@@ -513,9 +878,134 @@ return true;
             Let,
             Ident("Здравствуйте"),
             Assign,
-            Number("100"),
+            Int("100"),
             Semicolon
         ];
         test_lexer(input, expected_tokens);
     }
+
+    #[test]
+    fn comment_at_end_test() {
+        let input = "let x = 5;\n// trailing comment";
+        let expected_tokens = &[Let, Ident("x"), Assign, Int("5"), Semicolon];
+        test_lexer(input, expected_tokens);
+    }
+
+    #[test]
+    fn illegal_test() {
+        let input = "let $ = 5 @ 🐒;";
+        let expected_tokens = &[
+            Let,
+            Illegal("$"),
+            Assign,
+            Int("5"),
+            Illegal("@"),
+            Illegal("🐒"),
+            Semicolon,
+        ];
+        test_lexer(input, expected_tokens);
+    }
+
+    #[test]
+    fn number_test() {
+        let input = "1 1_000 1.5 2.5e-3 1e10 1E+2 0xFF 0o17 0b10_10 12abc 0b2 1. .5 1.x 1e";
+        let expected_tokens = &[
+            Int("1"),
+            Int("1_000"),
+            Float("1.5"),
+            Float("2.5e-3"),
+            Float("1e10"),
+            Float("1E+2"),
+            Int("0xFF"),
+            Int("0o17"),
+            Int("0b10_10"),
+            Int("12abc"),
+            Int("0b2"),
+            Int("1"),
+            Illegal("."),
+            Illegal("."),
+            Int("5"),
+            Int("1"),
+            Illegal("."),
+            Ident("x"),
+            Int("1e"),
+        ];
+        test_lexer(input, expected_tokens);
+    }
+
+    #[test]
+    fn string_test() {
+        let input = r#"let s = "a \"b\" \\"; "unterminated \"#;
+        let expected_spans = &[
+            (Let, 0..3),
+            (Ident("s"), 4..5),
+            (Assign, 6..7),
+            (Str(r#""a \"b\" \\""#), 8..20),
+            (Semicolon, 20..21),
+            (Str(r#""unterminated \"#), 22..37),
+        ];
+        test_spans(input, expected_spans);
+    }
+
+    #[test]
+    fn spans_test() {
+        let input = "let five = 5;\n  five >= 10;";
+        let expected_spans = &[
+            (Let, 0..3),
+            (Ident("five"), 4..8),
+            (Assign, 9..10),
+            (Int("5"), 11..12),
+            (Semicolon, 12..13),
+            (Ident("five"), 16..20),
+            (GtEq, 21..23),
+            (Int("10"), 24..26),
+            (Semicolon, 26..27),
+        ];
+        test_spans(input, expected_spans);
+    }
+
+    #[test]
+    fn comment_spans_test() {
+        let input = "// comment\n20 / 2;";
+        let expected_spans = &[
+            (Int("20"), 11..13),
+            (Slash, 14..15),
+            (Int("2"), 16..17),
+            (Semicolon, 17..18),
+        ];
+        test_spans(input, expected_spans);
+    }
+
+    #[test]
+    fn many_comments_test() {
+        let input = format!("{}{}1", "// c\n".repeat(200_000), "/**/".repeat(200_000));
+        let tokens: Vec<_> = Lexer::new(&input).map(|token| token.node).collect();
+        assert_eq!(tokens, &[Int("1")]);
+    }
+
+    #[test]
+    fn unicode_spans_test() {
+        let input = "let Здравствуйте = 100;";
+        let expected_spans = &[
+            (Let, 0..3),
+            (Ident("Здравствуйте"), 4..28),
+            (Assign, 29..30),
+            (Int("100"), 31..34),
+            (Semicolon, 34..35),
+        ];
+        test_spans(input, expected_spans);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn token_json() {
+        let tokens: Vec<_> = Lexer::new("let x").collect();
+        assert_eq!(
+            serde_json::to_value(&tokens).unwrap(),
+            serde_json::json!([
+                { "kind": "Let", "text": "let", "span": { "start": 0, "end": 3 } },
+                { "kind": "Ident", "text": "x", "span": { "start": 4, "end": 5 } },
+            ])
+        );
+    }
 }
@@ -0,0 +1,135 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum NumberError {
+    #[error("Integer literal `{0}` is too large, the largest integer is 9223372036854775807")]
+    IntOverflow(String),
+
+    #[error("Float literal `{0}` is out of range")]
+    FloatOverflow(String),
+
+    #[error("Invalid digit `{digit}` in {base} literal `{literal}`")]
+    InvalidDigit {
+        digit: char,
+        base: &'static str,
+        literal: String,
+    },
+
+    #[error("Number literal `{0}` has no digits")]
+    NoDigits(String),
+
+    #[error("Invalid float literal `{0}`")]
+    InvalidFloat(String),
+}
+
+/// The radix and name of an integer literal, given its prefix
+fn radix(raw: &str) -> (u32, &'static str, &str) {
+    match raw.get(..2) {
+        Some("0x") | Some("0X") => (16, "hexadecimal", &raw[2..]),
+        Some("0o") | Some("0O") => (8, "octal", &raw[2..]),
+        Some("0b") | Some("0B") => (2, "binary", &raw[2..]),
+        _ => (10, "decimal", raw),
+    }
+}
+
+/// Decodes the source text of an `Int` token, like `1_000`, `0xff`, `0o17` or `0b1010`
+pub fn parse_int(raw: &str) -> Result<i64, NumberError> {
+    let (radix, base, digits) = radix(raw);
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    if digits.is_empty() {
+        return Err(NumberError::NoDigits(raw.to_string()));
+    }
+    if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Err(NumberError::InvalidDigit {
+            digit,
+            base,
+            literal: raw.to_string(),
+        });
+    }
+    i64::from_str_radix(&digits, radix).map_err(|_| NumberError::IntOverflow(raw.to_string()))
+}
+
+/// Decodes the source text of a `Float` token, like `1.5`, `2e10` or `1_000.25e-3`
+pub fn parse_float(raw: &str) -> Result<f64, NumberError> {
+    let digits: String = raw.chars().filter(|&c| c != '_').collect();
+    if let Some(digit) = digits
+        .chars()
+        .find(|c| !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-')))
+    {
+        return Err(NumberError::InvalidDigit {
+            digit,
+            base: "float",
+            literal: raw.to_string(),
+        });
+    }
+    match digits.parse::<f64>() {
+        Ok(x) if x.is_finite() => Ok(x),
+        Ok(_) => Err(NumberError::FloatOverflow(raw.to_string())),
+        Err(_) => Err(NumberError::InvalidFloat(raw.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_int_test() {
+        assert_eq!(parse_int("0"), Ok(0));
+        assert_eq!(parse_int("1_000_000"), Ok(1_000_000));
+        assert_eq!(parse_int("0xff"), Ok(255));
+        assert_eq!(parse_int("0XFF_FF"), Ok(0xffff));
+        assert_eq!(parse_int("0o17"), Ok(15));
+        assert_eq!(parse_int("0b1010"), Ok(10));
+        assert_eq!(parse_int("9223372036854775807"), Ok(i64::MAX));
+    }
+
+    #[test]
+    fn parse_int_errors() {
+        assert_eq!(
+            parse_int("9223372036854775808"),
+            Err(NumberError::IntOverflow("9223372036854775808".into()))
+        );
+        assert_eq!(
+            parse_int("0x1_0000_0000_0000_0000"),
+            Err(NumberError::IntOverflow("0x1_0000_0000_0000_0000".into()))
+        );
+        assert_eq!(parse_int("0x"), Err(NumberError::NoDigits("0x".into())));
+        assert_eq!(parse_int("0b_"), Err(NumberError::NoDigits("0b_".into())));
+        assert_eq!(
+            parse_int("0b102"),
+            Err(NumberError::InvalidDigit {
+                digit: '2',
+                base: "binary",
+                literal: "0b102".into()
+            })
+        );
+        assert_eq!(
+            parse_int("12abc"),
+            Err(NumberError::InvalidDigit {
+                digit: 'a',
+                base: "decimal",
+                literal: "12abc".into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_float_test() {
+        assert_eq!(parse_float("1.5"), Ok(1.5));
+        assert_eq!(parse_float("1_000.25"), Ok(1000.25));
+        assert_eq!(parse_float("2e3"), Ok(2000.0));
+        assert_eq!(parse_float("2.5E-1"), Ok(0.25));
+        assert_eq!(parse_float("1e+2"), Ok(100.0));
+        assert_eq!(parse_float("1e999"), Err(NumberError::FloatOverflow("1e999".into())));
+        assert_eq!(parse_float("1.5e"), Err(NumberError::InvalidFloat("1.5e".into())));
+        assert_eq!(
+            parse_float("1.5x"),
+            Err(NumberError::InvalidDigit {
+                digit: 'x',
+                base: "float",
+                literal: "1.5x".into()
+            })
+        );
+    }
+}
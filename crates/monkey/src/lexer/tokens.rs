@@ -2,11 +2,26 @@ use std::fmt;
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Token<'a> {
-    Illegal,
+    Illegal(&'a str),
+    /// a `/*` without a matching `*/`, running until the end of the input
+    UnterminatedComment(&'a str),
+    /// a `///` comment up to the end of the line
+    DocComment(&'a str),
+
+    // trivia, only emitted by a lossless lexer
+    /// a run of whitespace, including line breaks
+    Whitespace(&'a str),
+    /// a `//` comment up to, but not including, the line break
+    Comment(&'a str),
+    /// a terminated `/* */` comment
+    BlockComment(&'a str),
 
     // identifies + literals
     Ident(&'a str),
-    Number(&'a str),
+    Int(&'a str),
+    Float(&'a str),
+    /// the source text of a string literal including the quotes, escapes are decoded by the parser
+    Str(&'a str),
 
     // operators
     Assign,
@@ -15,6 +30,14 @@ pub enum Token<'a> {
     Bang,
     Asterisk,
     Slash,
+    Percent,
+    And,
+    Or,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    PercentAssign,
     Lt,
     Gt,
     LtEq,
@@ -23,11 +46,14 @@ pub enum Token<'a> {
     // delimiters
     Comma,
     Semicolon,
+    Colon,
 
     Lparen,
     Rparen,
     Lbrace,
     Rbrace,
+    Lbracket,
+    Rbracket,
 
     // keywords
     Function,
@@ -43,35 +69,163 @@ pub enum Token<'a> {
 }
 
 impl<'a> Token<'a> {
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'a str {
         use Token::*;
 
-        match self._as_static_str() {
-            Ok(s) => s,
-            Err(token) => match token {
-                Ident(s) => s,
-                Number(s) => s,
-                _ => unreachable!(),
-            }
+        match *self {
+            Illegal(s) | UnterminatedComment(s) | DocComment(s) | Whitespace(s) | Comment(s)
+            | BlockComment(s) | Ident(s) | Int(s) | Float(s) | Str(s) => s,
+            _ => self.as_static_str().unwrap_or_default(),
         }
     }
 
-    /// # panics
-    /// will panic if the token cannot be turned into a static string
-    pub fn as_static_str(&self) -> &'static str {
-        self._as_static_str().unwrap()
+    /// Whitespace and comments that carry no meaning, doc comments are not trivia
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace(_) | Token::Comment(_) | Token::BlockComment(_))
     }
 
-    pub fn _as_static_str(&self) -> Result<&'static str, Token<'_>> {
-        let s = match self {
-            Illegal => "ILLEGAL",
+    /// The operator a compound assignment like `+=` applies, or `None` for any other token
+    pub fn compound_operator(&self) -> Option<Token<'static>> {
+        use Token::*;
+
+        let operator = match self {
+            PlusAssign => Plus,
+            MinusAssign => Minus,
+            AsteriskAssign => Asterisk,
+            SlashAssign => Slash,
+            PercentAssign => Percent,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    /// The name of the variant, used as the `kind` of the token in serialized output
+    pub fn kind(&self) -> &'static str {
+        use Token::*;
+
+        match self {
+            Illegal(_) => "Illegal",
+            UnterminatedComment(_) => "UnterminatedComment",
+            DocComment(_) => "DocComment",
+            Whitespace(_) => "Whitespace",
+            Comment(_) => "Comment",
+            BlockComment(_) => "BlockComment",
+            Ident(_) => "Ident",
+            Int(_) => "Int",
+            Float(_) => "Float",
+            Str(_) => "Str",
+            Assign => "Assign",
+            Plus => "Plus",
+            Minus => "Minus",
+            Bang => "Bang",
+            Asterisk => "Asterisk",
+            Slash => "Slash",
+            Percent => "Percent",
+            And => "And",
+            Or => "Or",
+            PlusAssign => "PlusAssign",
+            MinusAssign => "MinusAssign",
+            AsteriskAssign => "AsteriskAssign",
+            SlashAssign => "SlashAssign",
+            PercentAssign => "PercentAssign",
+            Lt => "Lt",
+            Gt => "Gt",
+            LtEq => "LtEq",
+            GtEq => "GtEq",
+            Comma => "Comma",
+            Semicolon => "Semicolon",
+            Colon => "Colon",
+            Lparen => "Lparen",
+            Rparen => "Rparen",
+            Lbrace => "Lbrace",
+            Rbrace => "Rbrace",
+            Lbracket => "Lbracket",
+            Rbracket => "Rbracket",
+            Function => "Function",
+            Let => "Let",
+            True => "True",
+            False => "False",
+            If => "If",
+            Else => "Else",
+            Return => "Return",
+            Eq => "Eq",
+            NotEq => "NotEq",
+        }
+    }
+
+    /// Converts the token to one that does not borrow from the input, which is possible for every
+    /// token except identifiers, literals, comments and illegal tokens
+    pub fn to_static(&self) -> Option<Token<'static>> {
+        use Token::*;
+
+        let token = match *self {
+            Illegal(_) | UnterminatedComment(_) | DocComment(_) | Whitespace(_) | Comment(_)
+            | BlockComment(_) | Ident(_) | Int(_) | Float(_) | Str(_) => return None,
 
+            Assign => Assign,
+            Plus => Plus,
+            Minus => Minus,
+            Bang => Bang,
+            Asterisk => Asterisk,
+            Slash => Slash,
+            Percent => Percent,
+            And => And,
+            Or => Or,
+            PlusAssign => PlusAssign,
+            MinusAssign => MinusAssign,
+            AsteriskAssign => AsteriskAssign,
+            SlashAssign => SlashAssign,
+            PercentAssign => PercentAssign,
+            Lt => Lt,
+            Gt => Gt,
+            LtEq => LtEq,
+            GtEq => GtEq,
+
+            Comma => Comma,
+            Semicolon => Semicolon,
+            Colon => Colon,
+
+            Lparen => Lparen,
+            Rparen => Rparen,
+            Lbrace => Lbrace,
+            Rbrace => Rbrace,
+            Lbracket => Lbracket,
+            Rbracket => Rbracket,
+
+            Function => Function,
+            Let => Let,
+            True => True,
+            False => False,
+            If => If,
+            Else => Else,
+            Return => Return,
+
+            Eq => Eq,
+            NotEq => NotEq,
+        };
+        Some(token)
+    }
+
+    /// Returns the text of the token if it is always the same, which is the case for everything
+    /// except identifiers, literals, comments and illegal tokens
+    pub fn as_static_str(&self) -> Option<&'static str> {
+        use Token::*;
+
+        let s = match self {
             Assign => "=",
             Plus => "+",
             Minus => "-",
             Bang => "!",
             Asterisk => "*",
             Slash => "/",
+            Percent => "%",
+            And => "&&",
+            Or => "||",
+            PlusAssign => "+=",
+            MinusAssign => "-=",
+            AsteriskAssign => "*=",
+            SlashAssign => "/=",
+            PercentAssign => "%=",
             Lt => "<",
             Gt => ">",
             LtEq => "<=",
@@ -79,11 +233,14 @@ impl<'a> Token<'a> {
 
             Comma => ",",
             Semicolon => ";",
+            Colon => ":",
 
             Lparen => "(",
             Rparen => ")",
             Lbrace => "{",
             Rbrace => "}",
+            Lbracket => "[",
+            Rbracket => "]",
 
             // keywords
             Function => "fn",
@@ -97,9 +254,23 @@ impl<'a> Token<'a> {
             Eq => "==",
             NotEq => "!=",
 
-            _ => return Err(*self),
+            Illegal(_) | UnterminatedComment(_) | DocComment(_) | Whitespace(_) | Comment(_)
+            | BlockComment(_) | Ident(_) | Int(_) | Float(_) | Str(_) => return None,
         };
-        Ok(s)
+        Some(s)
+    }
+}
+
+/// Serializes as `{"kind": "Ident", "text": "x"}`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Token<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut token = serializer.serialize_struct("Token", 2)?;
+        token.serialize_field("kind", self.kind())?;
+        token.serialize_field("text", self.as_str())?;
+        token.end()
     }
 }
 
@@ -0,0 +1,134 @@
+use thiserror::Error;
+
+use crate::common::{Span, Spanned};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EscapeError {
+    #[error("Unterminated string")]
+    Unterminated,
+
+    #[error("Unknown escape sequence `\\{0}`")]
+    UnknownEscape(char),
+
+    #[error("Invalid unicode escape, expected `\\u{{...}}` with 1 to 6 hex digits")]
+    BadUnicodeEscape,
+
+    #[error("`{0:X}` is not a valid unicode scalar value")]
+    InvalidCodePoint(u32),
+}
+
+/// Decodes the source text of a string token, including its quotes. The span of an error is
+/// relative to the start of `raw`.
+pub fn unescape(raw: &str) -> Result<String, Spanned<EscapeError>> {
+    let mut chars = raw.char_indices().skip(1).peekable();
+    let mut res = String::with_capacity(raw.len());
+
+    while let Some((idx, c)) = chars.next() {
+        let escape = match c {
+            '"' => return Ok(res),
+            '\\' => chars.next(),
+            _ => {
+                res.push(c);
+                continue;
+            }
+        };
+        let escaped = match escape {
+            Some((_, 'n')) => '\n',
+            Some((_, 't')) => '\t',
+            Some((_, '\\')) => '\\',
+            Some((_, '"')) => '"',
+            Some((_, 'u')) => {
+                let mut end = idx + 2;
+                let mut unicode = || -> Option<u32> {
+                    let (_, brace) = chars.next_if(|&(_, c)| c == '{')?;
+                    end += brace.len_utf8();
+                    let mut digits = String::new();
+                    while let Some((_, c)) = chars.next_if(|&(_, c)| c != '}' && c != '"') {
+                        end += c.len_utf8();
+                        digits.push(c);
+                    }
+                    let (_, brace) = chars.next_if(|&(_, c)| c == '}')?;
+                    end += brace.len_utf8();
+                    if digits.is_empty() || digits.len() > 6 {
+                        return None;
+                    }
+                    u32::from_str_radix(&digits, 16).ok()
+                };
+                let code = unicode();
+                let span = Span::new(idx, end);
+                let code = code.ok_or_else(|| Spanned::new(EscapeError::BadUnicodeEscape, span))?;
+                char::from_u32(code)
+                    .ok_or_else(|| Spanned::new(EscapeError::InvalidCodePoint(code), span))?
+            }
+            Some((_, c)) => {
+                let span = Span::new(idx, idx + 1 + c.len_utf8());
+                return Err(Spanned::new(EscapeError::UnknownEscape(c), span));
+            }
+            None => break,
+        };
+        res.push(escaped);
+    }
+
+    // the opening quote is the most useful place to point at
+    Err(Spanned::new(EscapeError::Unterminated, Span::new(0, 1)))
+}
+
+/// Escapes the string so that it can be put between quotes in source code
+pub fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\\' => res.push_str("\\\\"),
+            '"' => res.push_str("\\\""),
+            _ if c.is_control() => res.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_unescape_err(raw: &str, err: EscapeError, span: Span) {
+        assert_eq!(unescape(raw), Err(Spanned::new(err, span)));
+    }
+
+    #[test]
+    fn unescape_test() {
+        assert_eq!(unescape(r#""""#).unwrap(), "");
+        assert_eq!(unescape(r#""hello""#).unwrap(), "hello");
+        assert_eq!(unescape(r#""a\nb\tc\\d\"e""#).unwrap(), "a\nb\tc\\d\"e");
+        assert_eq!(unescape(r#""\u{41}\u{1F600}\u{e9}""#).unwrap(), "A😀é");
+        assert_eq!(unescape(r#""Здравствуйте""#).unwrap(), "Здравствуйте");
+    }
+
+    #[test]
+    fn unescape_errors() {
+        test_unescape_err(r#""abc"#, EscapeError::Unterminated, Span::new(0, 1));
+        test_unescape_err(r#""abc\""#, EscapeError::Unterminated, Span::new(0, 1));
+        test_unescape_err(r#""ab\"#, EscapeError::Unterminated, Span::new(0, 1));
+        test_unescape_err(r#""a\qb""#, EscapeError::UnknownEscape('q'), Span::new(2, 4));
+        test_unescape_err(r#""\u41""#, EscapeError::BadUnicodeEscape, Span::new(1, 3));
+        test_unescape_err(r#""\u{}""#, EscapeError::BadUnicodeEscape, Span::new(1, 5));
+        test_unescape_err(r#""\u{1234567}""#, EscapeError::BadUnicodeEscape, Span::new(1, 12));
+        test_unescape_err(r#""\u{xyz}""#, EscapeError::BadUnicodeEscape, Span::new(1, 8));
+        test_unescape_err(r#""\u{41""#, EscapeError::BadUnicodeEscape, Span::new(1, 6));
+        test_unescape_err(
+            r#""\u{D800}""#,
+            EscapeError::InvalidCodePoint(0xD800),
+            Span::new(1, 9),
+        );
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for s in &["", "hello", "a\nb\tc\\d\"e", "\u{0}\u{7f}", "😀"] {
+            let raw = format!("\"{}\"", escape(s));
+            assert_eq!(&unescape(&raw).unwrap(), s);
+        }
+    }
+}
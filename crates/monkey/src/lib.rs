@@ -1,7 +1,15 @@
-mod parser;
-mod lexer;
-mod common;
-mod ast;
+pub mod parser;
+pub mod lexer;
+pub mod common;
+pub mod ast;
+pub mod diagnostic;
+pub mod eval;
+pub mod compiler;
+pub mod vm;
+mod interpreter;
+
+pub use eval::{FromObject, NativeFunction, Object, RuntimeError};
+pub use interpreter::{Interpreter, InterpreterError};
 
 #[cfg(test)]
 mod tests {
@@ -1,9 +1,12 @@
 mod parse_error;
+#[allow(clippy::module_inception)]
 mod parser;
 mod parse;
+mod precedence;
 
-pub use parser::Parser;
+pub use parser::{parse, parse_with_errors, Parser, MAX_DEPTH};
 pub use parse::Parse;
+pub use precedence::Precedence;
 pub use parse_error::{ParseResult, ParseError};
 
 #[cfg(test)]
@@ -1,15 +1,25 @@
-use crate::lexer::Token;
 use thiserror::Error;
 
+use crate::lexer::{EscapeError, LexError, NumberError};
+
 pub type ParseResult<T, E = ParseError> = Result<T, E>;
 
+/// Formats the expected tokens as "`a`" or "one of `a`, `b`"
+fn one_of(expected: &[String]) -> String {
+    let quoted: Vec<_> = expected.iter().map(|s| format!("`{}`", s)).collect();
+    match quoted.as_slice() {
+        [single] => single.clone(),
+        _ => format!("one of {}", quoted.join(", ")),
+    }
+}
+
 #[derive(Error, Debug, Clone, PartialEq)]
 pub enum ParseError {
     #[error("{0}")]
     Custom(&'static str),
 
-    #[error("Bad number")]
-    BadNumber,
+    #[error(transparent)]
+    BadNumber(#[from] NumberError),
 
     #[error("Unexpected end of file")]
     UnexpectedEof,
@@ -32,9 +42,36 @@ pub enum ParseError {
         got: String,
     },
 
-    #[error("Expected token `{token}`, got token `{got}`")]
-    Expected {
-        token: &'static str,
-        got: &'static str,
-    }
+    #[error("Expected {}, got `{got}`", one_of(expected))]
+    UnexpectedToken {
+        got: String,
+        expected: Vec<String>,
+    },
+
+    #[error("Illegal character `{0}`")]
+    IllegalCharacter(String),
+
+    #[error("Expression is nested too deeply")]
+    NestingTooDeep,
+
+    #[error("Expected identifier, got `{got}`")]
+    ExpectedIdent {
+        got: String,
+    },
+
+    #[error("Cannot assign to `{target}`, only variables can be assigned to")]
+    InvalidAssignmentTarget {
+        target: String,
+    },
+
+    #[error("Expected expression, got `{got}`")]
+    ExpectedExpression {
+        got: String,
+    },
+
+    #[error(transparent)]
+    BadString(#[from] EscapeError),
+
+    #[error(transparent)]
+    Lex(#[from] LexError),
 }
@@ -1,16 +1,37 @@
 use super::{Parse, ParseError, ParseResult};
 use crate::ast;
-use crate::common::{Accept, Peekable};
+use crate::common::{Peekable, Span, Spanned};
 use crate::lexer::AdvancedLexer;
-use crate::lexer::Token::{self, *};
+use crate::lexer::Token;
+
+/// How many blocks, brackets, prefix operators and assignments can be nested before the parser
+/// gives up, so that pathological input cannot overflow the stack
+pub const MAX_DEPTH: usize = 256;
 
 pub struct Parser<'input> {
     pub lexer: AdvancedLexer<'input>,
-    pub errors: Vec<ParseError>,
+    pub errors: Vec<Spanned<ParseError>>,
+    depth: usize,
+    /// overrides the span of the next recorded error, see `error_at`
+    error_span: Option<Span>,
+}
+
+/// Parses a whole program, returning the first error if there were any.
+pub fn parse(s: &str) -> ParseResult<ast::Program, Spanned<ParseError>> {
+    let (program, mut errors) = parse_with_errors(s);
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors.remove(0))
+    }
 }
 
-pub fn parse(s: &str) -> ParseResult<ast::Program<'_>> {
-    Parser::new(s).parse()
+/// Parses a whole program, recovering from errors. The program contains `Statement::Error` for
+/// every statement that could not be parsed.
+pub fn parse_with_errors(s: &str) -> (ast::Program, Vec<Spanned<ParseError>>) {
+    let mut parser = Parser::new(s);
+    let program = parser.parse_program();
+    (program, parser.errors)
 }
 
 impl<'input> Parser<'input> {
@@ -19,6 +40,8 @@ impl<'input> Parser<'input> {
         Parser {
             lexer,
             errors: Vec::new(),
+            depth: 0,
+            error_span: None,
         }
     }
 
@@ -26,23 +49,89 @@ impl<'input> Parser<'input> {
         &mut self.lexer
     }
 
+    /// Parses the whole input. Afterwards `errors` also contains the errors found by the lexer,
+    /// in source order.
+    pub fn parse_program(&mut self) -> ast::Program {
+        let program = self.parse().unwrap_or_else(|err| {
+            self.record(err);
+            ast::Program::default()
+        });
+        let lex_errors = self.lexer.take_errors();
+        self.errors
+            .extend(lex_errors.into_iter().map(|err| err.map(ParseError::from)));
+        self.errors.sort_by_key(|err| err.span.start);
+        program
+    }
+
+    /// Records the error with the span of the current token, or the span given to `error_at`
+    pub fn record(&mut self, err: ParseError) {
+        let span = self
+            .error_span
+            .take()
+            .unwrap_or_else(|| self.lexer.curr_span());
+        self.errors.push(Spanned::new(err, span));
+    }
+
+    /// Returns the error so it can be propagated, making it point at `span` instead of the
+    /// current token once it is recorded
+    pub fn error_at(&mut self, err: ParseError, span: Span) -> ParseError {
+        self.error_span = Some(span);
+        err
+    }
+
+    /// Skips tokens until the end of the current statement, which is either after a `;` or before
+    /// a `}` that closes the enclosing block. Nested blocks are skipped over as a whole. If the
+    /// failed statement, which started at `start`, consumed no tokens, at least one token is
+    /// skipped so that recovery always makes progress.
+    pub fn synchronize(&mut self, start: Span) {
+        let mut depth = 0usize;
+        if self.lexer.peek_span() == start {
+            match self.lexer.next().map(|token| token.node) {
+                Some(Token::Lbrace) => depth += 1,
+                Some(Token::Semicolon) | None => return,
+                _ => (),
+            }
+        } else if self.lexer.curr_token().map(|token| token.node) == Some(Token::Semicolon) {
+            return;
+        }
+
+        while let Some(token) = self.lexer.peek().map(|token| token.node) {
+            match token {
+                Token::Lbrace => depth += 1,
+                Token::Rbrace if depth == 0 => return,
+                Token::Rbrace => depth -= 1,
+                Token::Semicolon if depth == 0 => {
+                    self.lexer.next();
+                    return;
+                }
+                _ => (),
+            }
+            self.lexer.next();
+        }
+    }
+
     pub fn expect_or(&mut self, expected: Token<'_>, err: ParseError) -> ParseResult<()> {
-        if *self.lexer().peek().ok_or(err.clone())? == expected {
+        if self.lexer().peek().ok_or_else(|| err.clone())?.node == expected {
             Err(err)
         } else {
             Ok(())
         }
     }
 
-    pub fn next_or_err(&mut self) -> Result<Token<'_>, ParseError> {
+    pub fn next_or_err(&mut self) -> Result<Spanned<Token<'input>>, ParseError> {
         self.lexer.next().ok_or(ParseError::UnexpectedEof)
     }
 
-    pub fn peek_or_err(&mut self) -> Result<&Token<'_>, ParseError> {
+    pub fn peek_or_err(&mut self) -> Result<&Spanned<Token<'input>>, ParseError> {
         self.lexer.peek().ok_or(ParseError::UnexpectedEof)
     }
 
-    pub fn curr_token_or_err(&self) -> Result<Token<'input>, ParseError> {
+    /// Checks if the next token is `token` without consuming it
+    pub fn peek_is(&self, token: Token<'_>) -> bool {
+        self.lexer.peek().is_some_and(|next| next.node == token)
+    }
+
+    pub fn curr_token_or_err(&self) -> Result<Spanned<Token<'input>>, ParseError> {
         self.lexer.curr_token().ok_or(ParseError::UnexpectedEof)
     }
 
@@ -50,17 +139,70 @@ impl<'input> Parser<'input> {
         T::parse(self)
     }
 
+    /// Runs `f` one nesting level deeper, failing if the input is nested too deeply
+    pub fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParseError::NestingTooDeep);
+        }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
     pub fn expect<'a>(&mut self, token: Token<'a>) -> ParseResult<Token<'a>> {
-        let next = self.next_or_err()?;
+        let next = self.next_or_err()?.node;
         if next == token {
             Ok(token)
         } else {
-            Err(ParseError::Expected {
-                token: token.as_static_str(),
-                got: next.as_static_str(),
-            })
+            Err(unexpected(next, &[token]))
         }
     }
+
+    /// Parses items separated by commas until the `end` token, which is consumed. A trailing comma
+    /// is allowed.
+    pub fn parse_delimited<T>(
+        &mut self,
+        end: Token<'static>,
+        mut parse_item: impl FnMut(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<Vec<T>> {
+        self.nested(|p| {
+            let mut items = Vec::new();
+            loop {
+                if p.peek_is(end) {
+                    p.next_or_err()?;
+                    return Ok(items);
+                }
+                items.push(parse_item(p)?);
+                match p.next_or_err()?.node {
+                    Token::Comma => (),
+                    next if next == end => return Ok(items),
+                    next => return Err(unexpected(next, &[Token::Comma, end])),
+                }
+            }
+        })
+    }
+
+    pub fn expect_ident(&mut self) -> ParseResult<&'input str> {
+        let next = self.next_or_err()?.node;
+        match next {
+            Token::Ident(ident) => Ok(ident),
+            _ => Err(ParseError::ExpectedIdent {
+                got: next.to_string(),
+            }),
+        }
+    }
+}
+
+/// Creates an error for when `got` was found instead of one of the `expected` tokens
+pub fn unexpected(got: Token<'_>, expected: &[Token<'_>]) -> ParseError {
+    match got {
+        Token::Illegal(c) => ParseError::IllegalCharacter(c.to_string()),
+        _ => ParseError::UnexpectedToken {
+            got: got.to_string(),
+            expected: expected.iter().map(ToString::to_string).collect(),
+        },
+    }
 }
 
 // impl<'a> Peekable for Parser<'a> {
@@ -72,6 +214,8 @@ impl<'input> Parser<'input> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::Span;
+    use crate::lexer::LexError;
 
     #[test]
     fn parse_number() {
@@ -87,10 +231,458 @@ mod tests {
         assert_eq!(program.to_string(), "true;")
     }
 
+    fn test_parse(input: &str, expected: &str) {
+        let program = parse(input).unwrap();
+        assert_eq!(program.to_string(), expected);
+    }
+
+    fn test_parse_err(input: &str, expected: ParseError) {
+        let err = parse(input).unwrap_err();
+        assert_eq!(err.node, expected);
+    }
+
+    #[test]
+    fn parse_prefix() {
+        test_parse("-15;", "(-15);");
+        test_parse("!true;", "(!true);");
+        test_parse("!!x;", "(!(!x));");
+    }
+
+    #[test]
+    fn parse_infix() {
+        test_parse("5 + 5;", "(5 + 5);");
+        test_parse("5 - 5;", "(5 - 5);");
+        test_parse("5 * 5;", "(5 * 5);");
+        test_parse("5 / 5;", "(5 / 5);");
+        test_parse("5 < 5;", "(5 < 5);");
+        test_parse("5 >= 5;", "(5 >= 5);");
+        test_parse("5 == 5;", "(5 == 5);");
+        test_parse("true != false;", "(true != false);");
+    }
+
+    #[test]
+    fn parse_precedence() {
+        test_parse("-a * b;", "((-a) * b);");
+        test_parse("a + b - c;", "((a + b) - c);");
+        test_parse("a + b * c - d / e;", "((a + (b * c)) - (d / e));");
+        test_parse("5 > 4 == 3 < 4;", "((5 > 4) == (3 < 4));");
+        test_parse("3 + 4 * 5 == 3 * 1 + 4 * 5;", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)));");
+        test_parse("!(true == true);", "(!(true == true));");
+        test_parse("(5 + 5) * 2;", "((5 + 5) * 2);");
+        test_parse("-(5 + 5);", "(-(5 + 5));");
+    }
+
+    #[test]
+    fn parse_bad_prefix_operator() {
+        test_parse_err("* 5;", ParseError::BadPrefixOperator { op: "*".into() });
+    }
+
+    #[test]
+    fn parse_bad_postfix_operator() {
+        test_parse_err("5!;", ParseError::BadPostfixOperator { op: "!".into() });
+    }
+
+    #[test]
+    fn parse_unclosed_paren() {
+        test_parse_err(
+            "(5 + 5;",
+            ParseError::UnexpectedToken {
+                got: ";".into(),
+                expected: vec![")".into()],
+            },
+        );
+    }
+
+    #[test]
+    fn parse_let() {
+        test_parse("let x = 5;", "let x = 5;");
+        test_parse("let y = true;", "let y = true;");
+        test_parse("let foobar = y * -5;", "let foobar = (y * (-5));");
+    }
+
+    #[test]
+    fn parse_return() {
+        test_parse("return 5;", "return 5;");
+        test_parse("return x + y;", "return (x + y);");
+    }
+
+    #[test]
+    fn parse_let_round_trip() {
+        let program = parse("let x = 5;\nreturn x;").unwrap();
+        let printed = program.to_string();
+        let reparsed = parse(&printed).unwrap();
+        assert_eq!(reparsed.to_string(), printed);
+    }
+
+    #[test]
+    fn parse_let_bad_ident() {
+        test_parse_err("let 5 = x;", ParseError::ExpectedIdent { got: "5".into() });
+        test_parse_err("let let = x;", ParseError::ExpectedIdent { got: "let".into() });
+    }
+
+    #[test]
+    fn parse_let_no_assign() {
+        test_parse_err(
+            "let x 5;",
+            ParseError::UnexpectedToken {
+                got: "5".into(),
+                expected: vec!["=".into()],
+            },
+        );
+    }
+
+    #[test]
+    fn parse_missing_expression() {
+        test_parse_err("return;", ParseError::ExpectedExpression { got: ";".into() });
+        test_parse_err("let x = ;", ParseError::ExpectedExpression { got: ";".into() });
+        test_parse_err("return", ParseError::UnexpectedEof);
+    }
+
+    #[test]
+    fn parse_if() {
+        test_parse("if (x < y) { x }", "if (x < y) { x; }");
+        test_parse("if x { x; } else { y; }", "if x { x; } else { y; }");
+        test_parse("if (x) {} else {};", "if x {} else {}");
+        test_parse("let z = if (a) { 1 } else { 2 };", "let z = if a { 1; } else { 2; };");
+        test_parse(
+            "if (a) { 1 } else if (b) { 2 } else { 3 }",
+            "if a { 1; } else { if b { 2; } else { 3; } }",
+        );
+    }
+
+    #[test]
+    fn parse_long_else_if_chain() {
+        let mut input = String::from("if (x == 0) { 0 }");
+        for i in 1..200 {
+            input += &format!(" else if (x == {}) {{ {} }}", i, i);
+        }
+        input += " else { -1 }";
+        let program = parse(&input).unwrap();
+        assert_eq!(program.statements().len(), 1);
+    }
+
+    #[test]
+    fn parse_function() {
+        test_parse("fn(x, y) { x + y; };", "fn(x, y) { (x + y); };");
+        test_parse("fn() {};", "fn() {};");
+        test_parse("fn(x,) { return x; };", "fn(x) { return x; };");
+        test_parse(
+            "let add = fn(x, y) {\n    x + y;\n};",
+            "let add = fn(x, y) { (x + y); };",
+        );
+        test_parse("fn(x) { fn(y) { x + y } };", "fn(x) { fn(y) { (x + y); }; };");
+    }
+
+    #[test]
+    fn parse_call() {
+        test_parse("add(1, 2 * 3, 4 + 5);", "add(1, (2 * 3), (4 + 5));");
+        test_parse("a + add(b * c) + d;", "((a + add((b * c))) + d);");
+        test_parse("add();", "add();");
+        test_parse("fn(x) { x; }(5);", "fn(x) { x; }(5);");
+        test_parse("-f(x);", "(-f(x));");
+        test_parse("f(x)(y);", "f(x)(y);");
+    }
+
+    #[test]
+    fn parse_block_round_trip() {
+        let program = parse("let f = fn(x) { if (x > 1) { return x; } else { f(x - 1) } };").unwrap();
+        let printed = program.to_string();
+        assert_eq!(parse(&printed).unwrap().to_string(), printed);
+    }
+
+    #[test]
+    fn parse_function_bad_param() {
+        test_parse_err("fn(1) {};", ParseError::ExpectedIdent { got: "1".into() });
+    }
+
+    #[test]
+    fn parse_unclosed_block() {
+        test_parse_err("if (x) { x;", ParseError::UnexpectedEof);
+        test_parse_err(
+            "f(1, 2;",
+            ParseError::UnexpectedToken {
+                got: ";".into(),
+                expected: vec![",".into(), ")".into()],
+            },
+        );
+    }
+
+    #[test]
+    fn parse_missing_semicolon_between_statements() {
+        test_parse_err(
+            "let x = 5 let y = 6;",
+            ParseError::UnexpectedToken {
+                got: "let".into(),
+                expected: vec![";".into()],
+            },
+        );
+    }
+
+    fn test_parse_errors(input: &str, expected: &str, expected_errors: &[ParseError]) {
+        let (program, errors) = parse_with_errors(input);
+        let errors: Vec<_> = errors.into_iter().map(|err| err.node).collect();
+        assert_eq!(program.to_string(), expected);
+        assert_eq!(errors, expected_errors);
+    }
+
+    #[test]
+    fn recover_multiple_errors() {
+        test_parse_errors(
+            "let 5 = x; let y = ; let z = 10;",
+            "<error>\n<error>\nlet z = 10;",
+            &[
+                ParseError::ExpectedIdent { got: "5".into() },
+                ParseError::ExpectedExpression { got: ";".into() },
+            ],
+        );
+    }
+
+    #[test]
+    fn recover_in_block() {
+        test_parse_errors(
+            "let f = fn(x) { let = 5; x }; f(1);",
+            "let f = fn(x) { <error> x; };\nf(1);",
+            &[ParseError::ExpectedIdent { got: "=".into() }],
+        );
+    }
+
+    #[test]
+    fn recover_skips_nested_blocks() {
+        test_parse_errors(
+            "let x 5 + fn() { 1; 2; }; x;",
+            "<error>\nx;",
+            &[ParseError::UnexpectedToken {
+                got: "5".into(),
+                expected: vec!["=".into()],
+            }],
+        );
+    }
+
+    #[test]
+    fn recover_stray_rbrace() {
+        test_parse_errors(
+            "}; 5;",
+            "<error>\n5;",
+            &[ParseError::ExpectedExpression { got: "}".into() }],
+        );
+    }
+
+    #[test]
+    fn recover_unexpected_eof() {
+        test_parse_errors("let x = 5; let", "let x = 5;\n<error>", &[ParseError::UnexpectedEof]);
+    }
+
+    #[test]
+    fn recorded_error_spans() {
+        let (_, errors) = parse_with_errors("let 5 = x;\nlet y = ;");
+        let spans: Vec<_> = errors.iter().map(|err| err.span).collect();
+        assert_eq!(spans, &[Span::new(4, 5), Span::new(19, 20)]);
+    }
+
+    #[test]
+    fn parse_doc_comments() {
+        let input = "/// adds one\n///\n///  to x\nlet add = fn(x) {\n    /// inner\n    let y = 1;\n    /// dropped\n    x + y\n};\n/// dropped too\nadd(1);\nlet z = 2;";
+        let program = parse(input).unwrap();
+        let docs = |stmt: &ast::Statement| match stmt {
+            ast::Statement::Let { doc, .. } => doc.clone(),
+            _ => None,
+        };
+        let statements = program.statements();
+        assert_eq!(docs(&statements[0].node).as_deref(), Some("adds one\n\n to x"));
+        assert_eq!(docs(&statements[2].node), None);
+        match &statements[0].node {
+            ast::Statement::Let {
+                value: ast::Expression::Function { body, .. },
+                ..
+            } => assert_eq!(docs(&body.statements[0].node).as_deref(), Some("inner")),
+            stmt => panic!("expected a function, got {}", stmt),
+        }
+    }
+
+    #[test]
+    fn parse_block_comments() {
+        test_parse("let /* the answer */ x = 42 /* */;", "let x = 42;");
+        let (program, errors) = parse_with_errors("let x = 1;\nlet y = /* 2;");
+        assert_eq!(program.to_string(), "let x = 1;\n<error>");
+        assert_eq!(
+            errors,
+            &[
+                Spanned::new(LexError::UnterminatedComment.into(), Span::new(19, 21)),
+                Spanned::new(ParseError::UnexpectedEof, Span::new(24, 24)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_numbers() {
+        test_parse("0xff + 1_000;", "(255 + 1000);");
+        test_parse("1.5 * 2e3;", "(1.5 * 2000.0);");
+        test_parse("let x = 1e100;", "let x = 1e100;");
+        let program = parse("2.5;").unwrap();
+        assert_eq!(
+            program.statements()[0].node,
+            ast::Statement::Expression(ast::Expression::FloatLiteral(2.5))
+        );
+    }
+
+    #[test]
+    fn parse_bad_numbers() {
+        use crate::lexer::NumberError;
+
+        test_parse_err(
+            "9223372036854775808;",
+            NumberError::IntOverflow("9223372036854775808".into()).into(),
+        );
+        test_parse_err(
+            "0b102;",
+            NumberError::InvalidDigit {
+                digit: '2',
+                base: "binary",
+                literal: "0b102".into(),
+            }
+            .into(),
+        );
+        test_parse_err("1e999;", NumberError::FloatOverflow("1e999".into()).into());
+        test_parse_err("1.;", ParseError::IllegalCharacter(".".into()));
+        assert_eq!(
+            ParseError::from(NumberError::IntOverflow("9223372036854775808".into())).to_string(),
+            "Integer literal `9223372036854775808` is too large, the largest integer is \
+             9223372036854775807"
+        );
+    }
+
+    #[test]
+    fn parse_string() {
+        test_parse(r#"let s = "a\nb";"#, r#"let s = "a\nb";"#);
+        test_parse(r#""a" + "\u{62}";"#, r#"("a" + "b");"#);
+        let program = parse(r#""\"\\\t";"#).unwrap();
+        assert_eq!(
+            program.statements()[0].node,
+            ast::Statement::Expression(ast::Expression::StringLiteral("\"\\\t".into()))
+        );
+    }
+
+    #[test]
+    fn parse_collections() {
+        test_parse("[];", "[];");
+        test_parse("[1, 2 * 2, [3],];", "[1, (2 * 2), [3]];");
+        test_parse("a[1 + 1];", "(a[(1 + 1)]);");
+        test_parse("a * [1, 2][b * c] * d;", "((a * ([1, 2][(b * c)])) * d);");
+        test_parse("-a[0];", "(-(a[0]));");
+        test_parse("f(x)[0](y);", "(f(x)[0])(y);");
+        test_parse("{};", "{};");
+        test_parse(r#"{"a": 1, 2 + 2: [], true: {}};"#, r#"{"a": 1, (2 + 2): [], true: {}};"#);
+        test_parse("let x = {1: 2};", "let x = {1: 2};");
+    }
+
+    #[test]
+    fn parse_logical_and_assign() {
+        test_parse("a || b && c == d;", "(a || (b && (c == d)));");
+        test_parse("a && b || c;", "((a && b) || c);");
+        test_parse("a + b % c * d;", "(a + ((b % c) * d));");
+        test_parse("x = y = 1 + 2;", "(x = (y = (1 + 2)));");
+        test_parse("x += y || z;", "(x += (y || z));");
+        test_parse("let x = y %= 2;", "let x = (y %= 2);");
+        test_parse("f(x = 1);", "f((x = 1));");
+        test_parse_err(
+            "a + b = c;",
+            ParseError::InvalidAssignmentTarget {
+                target: "(a + b)".into(),
+            },
+        );
+        test_parse_err(
+            "1 -= 2;",
+            ParseError::InvalidAssignmentTarget { target: "1".into() },
+        );
+        test_parse_err("a & b;", ParseError::IllegalCharacter("&".into()));
+    }
+
+    #[test]
+    fn parse_bad_collections() {
+        test_parse_err(
+            "[1 2];",
+            ParseError::UnexpectedToken {
+                got: "2".into(),
+                expected: vec![",".into(), "]".into()],
+            },
+        );
+        test_parse_err(
+            "{1, 2};",
+            ParseError::UnexpectedToken {
+                got: ",".into(),
+                expected: vec![":".into()],
+            },
+        );
+        test_parse_err(
+            "a[1;",
+            ParseError::UnexpectedToken {
+                got: ";".into(),
+                expected: vec!["]".into()],
+            },
+        );
+    }
+
+    #[test]
+    fn parse_bad_string() {
+        use crate::lexer::EscapeError;
+
+        let (_, errors) = parse_with_errors("let s = \"a\\qb\";\nlet t = \"abc");
+        assert_eq!(
+            errors,
+            &[
+                Spanned::new(EscapeError::UnknownEscape('q').into(), Span::new(10, 12)),
+                Spanned::new(EscapeError::Unterminated.into(), Span::new(24, 25)),
+            ]
+        );
+        test_parse_err(r#""\u{110000}";"#, EscapeError::InvalidCodePoint(0x110000).into());
+    }
+
+    #[test]
+    fn parse_illegal_character() {
+        test_parse_err("let x = $;", ParseError::IllegalCharacter("$".into()));
+        test_parse_err("let x = 5 @ 5;", ParseError::IllegalCharacter("@".into()));
+    }
+
+    #[test]
+    fn parse_nesting_too_deep() {
+        let input = format!("{}1{};", "(".repeat(10_000), ")".repeat(10_000));
+        test_parse_err(&input, ParseError::NestingTooDeep);
+
+        let input = format!("{}1;", "-".repeat(10_000));
+        test_parse_err(&input, ParseError::NestingTooDeep);
+
+        let input = format!("{}{}", "fn() {".repeat(10_000), "}".repeat(10_000));
+        let (_, errors) = parse_with_errors(&input);
+        assert_eq!(errors[0].node, ParseError::NestingTooDeep);
+    }
+
+    #[test]
+    fn parse_nesting_at_limit_recovers() {
+        let input = format!("{}(1); (2);{};", "fn() {".repeat(MAX_DEPTH), "}".repeat(MAX_DEPTH));
+        let (_, errors) = parse_with_errors(&input);
+        let errors: Vec<_> = errors.into_iter().map(|err| err.node).collect();
+        assert_eq!(errors, &[ParseError::NestingTooDeep, ParseError::NestingTooDeep]);
+    }
+
+    #[test]
+    fn parse_nesting_within_limit() {
+        let input = format!("{}1{};", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        test_parse(&input, "1;");
+    }
+
+    #[test]
+    fn unexpected_token_message() {
+        let err = ParseError::UnexpectedToken {
+            got: ";".into(),
+            expected: vec![",".into(), ")".into()],
+        };
+        assert_eq!(err.to_string(), "Expected one of `,`, `)`, got `;`");
+    }
+
     #[should_panic]
     #[test]
     fn parse_statement_no_end() {
         let s = "1234123";
-        let program = parse(s).unwrap();
+        parse(s).unwrap();
     }
 }
@@ -0,0 +1,41 @@
+use crate::lexer::Token;
+
+/// Binding power of operators, from loosest to tightest.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum Precedence {
+    Lowest,
+    Assign,
+    Or,
+    And,
+    Equals,
+    LessGreater,
+    Sum,
+    Product,
+    Prefix,
+    Call,
+    Index,
+}
+
+impl Precedence {
+    /// The precedence of the token when it is used as an infix operator, or `None` if the token is
+    /// not an infix operator
+    pub fn of_infix(token: &Token<'_>) -> Option<Precedence> {
+        use Token::*;
+
+        let precedence = match token {
+            Assign | PlusAssign | MinusAssign | AsteriskAssign | SlashAssign | PercentAssign => {
+                Precedence::Assign
+            }
+            Or => Precedence::Or,
+            And => Precedence::And,
+            Eq | NotEq => Precedence::Equals,
+            Lt | Gt | LtEq | GtEq => Precedence::LessGreater,
+            Plus | Minus => Precedence::Sum,
+            Asterisk | Slash | Percent => Precedence::Product,
+            Lparen => Precedence::Call,
+            Lbracket => Precedence::Index,
+            _ => return None,
+        };
+        Some(precedence)
+    }
+}
@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::compiler::CompiledFunction;
+use crate::eval::Object;
+
+/// A compiled function together with the variables it captured. Captured variables are shared
+/// with the function that defined them, so assignments are seen on both sides. They are `None`
+/// until the `let` that binds them runs.
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Rc<RefCell<Option<Object>>>>,
+}
+
+impl fmt::Debug for Closure {
+    // the free variables are left out because they can contain the closure itself
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Closure")
+            .field("function", &self.function.name)
+            .field("free", &self.free.len())
+            .finish()
+    }
+}
+
+impl PartialEq for Closure {
+    /// Closures are only equal to themselves
+    fn eq(&self, other: &Closure) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Display for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
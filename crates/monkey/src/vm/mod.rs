@@ -0,0 +1,6 @@
+mod closure;
+#[allow(clippy::module_inception)]
+mod vm;
+
+pub use closure::Closure;
+pub use vm::{run, Vm};
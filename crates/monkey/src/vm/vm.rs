@@ -0,0 +1,467 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use log::debug;
+
+use crate::common::Span;
+use crate::compiler::{read_instruction, Bytecode, Constant, Opcode};
+use crate::eval::{
+    build_hash, eval_index, eval_infix, eval_prefix, Object, BUILTINS, MAX_CALL_DEPTH,
+};
+
+use super::Closure;
+
+/// Runs the bytecode in a fresh vm
+pub fn run(bytecode: &Bytecode) -> Object {
+    Vm::new(bytecode).run()
+}
+
+/// A local variable, `None` until its `let` runs. It is moved into a shared cell when a closure
+/// captures it.
+#[derive(Debug)]
+enum Slot {
+    Value(Option<Object>),
+    Cell(Rc<RefCell<Option<Object>>>),
+}
+
+impl Slot {
+    fn get(&self) -> Option<Object> {
+        match self {
+            Slot::Value(value) => value.clone(),
+            Slot::Cell(cell) => cell.borrow().clone(),
+        }
+    }
+
+    fn set(&mut self, value: Object) {
+        match self {
+            Slot::Value(slot) => *slot = Some(value),
+            Slot::Cell(cell) => *cell.borrow_mut() = Some(value),
+        }
+    }
+
+    fn capture(&mut self) -> Rc<RefCell<Option<Object>>> {
+        if let Slot::Value(value) = self {
+            let value = value.take();
+            *self = Slot::Cell(Rc::new(RefCell::new(value)));
+        }
+        match self {
+            Slot::Cell(cell) => cell.clone(),
+            Slot::Value(_) => unreachable!("the slot was just moved into a cell"),
+        }
+    }
+}
+
+/// A function call that is being executed
+#[derive(Debug)]
+struct Frame {
+    closure: Rc<Closure>,
+    /// the offset of the next instruction
+    ip: usize,
+    /// where the locals of the call start in `Vm::locals`
+    locals: usize,
+    /// the height of the stack without the function and its arguments
+    stack: usize,
+}
+
+/// A stack machine that runs bytecode from `compiler::compile`. It gives the same results and
+/// runtime errors as the evaluator.
+#[derive(Debug)]
+pub struct Vm {
+    constants: Vec<Constant>,
+    global_names: Vec<String>,
+    globals: Vec<Option<Object>>,
+    stack: Vec<Object>,
+    locals: Vec<Slot>,
+    frames: Vec<Frame>,
+    error_span: Option<Span>,
+}
+
+impl Vm {
+    pub fn new(bytecode: &Bytecode) -> Vm {
+        let main = Closure {
+            function: bytecode.main.clone(),
+            free: Vec::new(),
+        };
+        let locals = (0..main.function.num_locals)
+            .map(|_| Slot::Value(None))
+            .collect();
+        Vm {
+            constants: bytecode.constants.clone(),
+            global_names: bytecode.globals.clone(),
+            globals: vec![None; bytecode.globals.len()],
+            stack: Vec::new(),
+            locals,
+            frames: vec![Frame {
+                closure: Rc::new(main),
+                ip: 0,
+                locals: 0,
+                stack: 0,
+            }],
+            error_span: None,
+        }
+    }
+
+    /// The span of the statement that produced the error, in the innermost function
+    pub fn error_span(&self) -> Option<Span> {
+        self.error_span
+    }
+
+    /// Runs the program and returns the value of the last statement, or of the first top level
+    /// `return`. Runtime errors are returned as `Object::Error`. A program only runs once, after
+    /// that this returns `null`.
+    pub fn run(&mut self) -> Object {
+        self.error_span = None;
+        match self.execute() {
+            Ok(value) => value,
+            Err(message) => {
+                self.error_span = self.frames.last().and_then(|frame| {
+                    // the instruction that failed ends at `ip`
+                    let offset = frame.ip.saturating_sub(1);
+                    frame.closure.function.span_at(offset)
+                });
+                self.frames.clear();
+                self.stack.clear();
+                self.locals.clear();
+                Object::Error(message)
+            }
+        }
+    }
+
+    fn execute(&mut self) -> Result<Object, String> {
+        loop {
+            let frame = match self.frames.last_mut() {
+                Some(frame) => frame,
+                None => return Ok(Object::Null),
+            };
+            let closure = Rc::clone(&frame.closure);
+            let code = &closure.function.instructions;
+            let offset = frame.ip;
+            let instruction = read_instruction(code, offset)
+                .ok_or_else(|| invalid(&format!("instruction at offset {}", offset)))?;
+            frame.ip = instruction.next;
+            let locals = frame.locals;
+            let operand = instruction.operands[0];
+
+            match instruction.op {
+                Opcode::Constant => {
+                    let value = match self.constants.get(operand) {
+                        Some(Constant::Integer(x)) => Object::Integer(*x),
+                        Some(Constant::Float(x)) => Object::Float(*x),
+                        Some(Constant::String(x)) => Object::String(x.clone()),
+                        _ => return Err(invalid("constant")),
+                    };
+                    self.stack.push(value);
+                }
+                Opcode::Null => self.stack.push(Object::Null),
+                Opcode::True => self.stack.push(Object::Boolean(true)),
+                Opcode::False => self.stack.push(Object::Boolean(false)),
+                Opcode::Pop => {
+                    self.pop()?;
+                }
+                Opcode::Dup => {
+                    let top = self.stack.last().ok_or_else(|| invalid("stack"))?.clone();
+                    self.stack.push(top);
+                }
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::Less
+                | Opcode::Greater
+                | Opcode::LessEqual
+                | Opcode::GreaterEqual => {
+                    let operator = instruction.op.operator().expect("a binary operator");
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    self.stack.push(check(eval_infix(operator, lhs, rhs))?);
+                }
+                Opcode::Minus | Opcode::Bang => {
+                    let operator = instruction.op.operator().expect("a prefix operator");
+                    let rhs = self.pop()?;
+                    self.stack.push(check(eval_prefix(operator, rhs))?);
+                }
+                Opcode::Jump => self.jump(operand),
+                Opcode::JumpIfFalse => {
+                    if !self.pop()?.is_truthy() {
+                        self.jump(operand);
+                    }
+                }
+                Opcode::GetGlobal => {
+                    let value = self.globals.get(operand).ok_or_else(|| invalid("global"))?;
+                    // like in the evaluator, the builtins come after all bindings
+                    let value = match value {
+                        Some(value) => value.clone(),
+                        None => self.builtin(operand)?,
+                    };
+                    self.stack.push(value);
+                }
+                Opcode::SetGlobal => {
+                    let value = self.pop()?;
+                    let global = self.globals.get_mut(operand).ok_or_else(|| invalid("global"))?;
+                    *global = Some(value);
+                }
+                Opcode::AssignGlobal => {
+                    let value = self.pop()?;
+                    match self.globals.get_mut(operand) {
+                        Some(Some(global)) => *global = value,
+                        Some(None) => return Err(self.not_found(operand)),
+                        None => return Err(invalid("global")),
+                    }
+                }
+                Opcode::GetLocal => {
+                    let slot = self.locals.get(locals + operand);
+                    let value = slot.and_then(Slot::get).ok_or_else(|| invalid("local"))?;
+                    self.stack.push(value);
+                }
+                Opcode::SetLocal => {
+                    let value = self.pop()?;
+                    let slot = self.locals.get_mut(locals + operand);
+                    slot.ok_or_else(|| invalid("local"))?.set(value);
+                }
+                Opcode::GetFree => {
+                    let cell = closure.free.get(operand).ok_or_else(|| invalid("free variable"))?;
+                    let value = cell.borrow().clone().ok_or_else(|| invalid("free variable"))?;
+                    self.stack.push(value);
+                }
+                Opcode::SetFree => {
+                    let value = self.pop()?;
+                    let cell = closure.free.get(operand).ok_or_else(|| invalid("free variable"))?;
+                    *cell.borrow_mut() = Some(value);
+                }
+                Opcode::TryGetLocal => {
+                    let slot = self.locals.get(locals + operand);
+                    if let Some(value) = slot.ok_or_else(|| invalid("local"))?.get() {
+                        self.stack.push(value);
+                        self.jump(instruction.operands[1]);
+                    }
+                }
+                Opcode::TryGetFree => {
+                    let cell = closure.free.get(operand).ok_or_else(|| invalid("free variable"))?;
+                    if let Some(value) = cell.borrow().clone() {
+                        self.stack.push(value);
+                        self.jump(instruction.operands[1]);
+                    }
+                }
+                Opcode::TryAssignLocal => {
+                    let slot = self.locals.get(locals + operand).ok_or_else(|| invalid("local"))?;
+                    if slot.get().is_some() {
+                        let value = self.pop()?;
+                        self.locals[locals + operand].set(value);
+                        self.jump(instruction.operands[1]);
+                    }
+                }
+                Opcode::TryAssignFree => {
+                    let cell = closure.free.get(operand).ok_or_else(|| invalid("free variable"))?;
+                    if cell.borrow().is_some() {
+                        let value = self.pop()?;
+                        *cell.borrow_mut() = Some(value);
+                        self.jump(instruction.operands[1]);
+                    }
+                }
+                Opcode::Array => {
+                    let items = self.pop_many(operand)?;
+                    self.stack.push(Object::Array(items));
+                }
+                Opcode::Hash => {
+                    let mut items = self.pop_many(2 * operand)?.into_iter();
+                    let mut pairs = Vec::with_capacity(operand);
+                    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                        pairs.push((key, value));
+                    }
+                    self.stack.push(check(build_hash(pairs))?);
+                }
+                Opcode::Index => {
+                    let index = self.pop()?;
+                    let left = self.pop()?;
+                    self.stack.push(check(eval_index(left, index))?);
+                }
+                Opcode::Call => self.call(operand)?,
+                Opcode::ReturnValue => {
+                    let value = self.pop()?;
+                    let frame = self.frames.pop().expect("the frame is running");
+                    if self.frames.is_empty() {
+                        self.stack.clear();
+                        return Ok(value);
+                    }
+                    self.locals.truncate(frame.locals);
+                    self.stack.truncate(frame.stack);
+                    self.stack.push(value);
+                }
+                Opcode::Closure => {
+                    let function = match self.constants.get(operand) {
+                        Some(Constant::Function(function)) => function.clone(),
+                        _ => return Err(invalid("function constant")),
+                    };
+                    let captures = &code[offset + 4..instruction.next];
+                    let mut free = Vec::with_capacity(captures.len() / 2);
+                    for capture in captures.chunks(2) {
+                        let index = usize::from(capture[1]);
+                        let cell = match capture[0] {
+                            1 => self.locals.get_mut(locals + index).map(Slot::capture),
+                            0 => closure.free.get(index).cloned(),
+                            _ => None,
+                        };
+                        free.push(cell.ok_or_else(|| invalid("captured variable"))?);
+                    }
+                    let closure = Closure { function, free };
+                    self.stack.push(Object::Closure(Rc::new(closure)));
+                }
+            }
+        }
+    }
+
+    /// Calls the function below the arguments on the stack. Compiled functions get a new frame,
+    /// builtins and native functions are called right away.
+    fn call(&mut self, num_args: usize) -> Result<(), String> {
+        let callee = self
+            .stack
+            .len()
+            .checked_sub(num_args + 1)
+            .ok_or_else(|| invalid("stack"))?;
+        match self.stack[callee].clone() {
+            Object::Closure(closure) => {
+                let num_params = closure.function.num_params;
+                if num_params != num_args {
+                    return Err(format!(
+                        "wrong number of arguments: expected {}, got {}",
+                        num_params, num_args
+                    ));
+                }
+                // the main frame is not a function call
+                if self.frames.len() > MAX_CALL_DEPTH {
+                    return Err("stack overflow: too many nested function calls".into());
+                }
+
+                debug!("calling {} at depth {}", closure, self.frames.len());
+                let locals = self.locals.len();
+                let num_locals = closure.function.num_locals.max(num_args);
+                self.locals
+                    .extend(self.stack.drain(callee + 1..).map(|arg| Slot::Value(Some(arg))));
+                self.locals
+                    .resize_with(locals + num_locals, || Slot::Value(None));
+                self.stack.truncate(callee);
+                self.frames.push(Frame {
+                    closure,
+                    ip: 0,
+                    locals,
+                    stack: callee,
+                });
+            }
+            Object::Builtin(builtin) => {
+                let args = self.stack.split_off(callee + 1);
+                self.stack.truncate(callee);
+                self.stack.push(check(builtin.call(&args))?);
+            }
+            Object::Native(native) => {
+                let args = self.stack.split_off(callee + 1);
+                self.stack.truncate(callee);
+                self.stack.push(native.call(&args).map_err(|err| err.message)?);
+            }
+            Object::Function(_) => {
+                return Err("functions of the evaluator cannot be called by the vm".into())
+            }
+            other => return Err(format!("not a function: {}", other.type_name())),
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, target: usize) {
+        self.frames.last_mut().expect("the frame is running").ip = target;
+    }
+
+    fn pop(&mut self) -> Result<Object, String> {
+        self.stack.pop().ok_or_else(|| invalid("stack"))
+    }
+
+    /// Pops the top `count` values, in the order they were pushed
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Object>, String> {
+        let start = self
+            .stack
+            .len()
+            .checked_sub(count)
+            .ok_or_else(|| invalid("stack"))?;
+        Ok(self.stack.split_off(start))
+    }
+
+    /// The builtin of the same name as the unbound global
+    fn builtin(&self, global: usize) -> Result<Object, String> {
+        let name = self.global_names.get(global).map_or("", String::as_str);
+        BUILTINS
+            .iter()
+            .find(|builtin| builtin.name == name)
+            .map(Object::Builtin)
+            .ok_or_else(|| self.not_found(global))
+    }
+
+    fn not_found(&self, global: usize) -> String {
+        let name = self.global_names.get(global).map_or("?", String::as_str);
+        format!("identifier not found: {}", name)
+    }
+}
+
+/// Turns the error objects returned by the evaluator's operators into errors
+fn check(value: Object) -> Result<Object, String> {
+    match value {
+        Object::Error(message) => Err(message),
+        value => Ok(value),
+    }
+}
+
+/// The error for bytecode that the compiler would not produce
+fn invalid(what: &str) -> String {
+    format!("invalid bytecode: bad {}", what)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::parser::parse;
+
+    fn test_run(input: &str) -> Object {
+        run(&compile(&parse(input).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn run_closures() {
+        let cases = [
+            ("let f = fn(a) { fn(b) { a + b } }; f(1)(2);", Object::Integer(3)),
+            (
+                "let counter = fn() { let n = 0; fn() { n += 1 } }; let c = counter(); c(); c();",
+                Object::Integer(2),
+            ),
+            (
+                "let f = fn() { let g = fn(n) { if (n == 0) { 0 } else { n + g(n - 1) } }; g(4) }; f();",
+                Object::Integer(10),
+            ),
+            ("let f = fn() { let x = 1; let g = fn() { x }; x = 2; g() }; f();", Object::Integer(2)),
+        ];
+        for (input, expected) in &cases {
+            assert_eq!(&test_run(input), expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn run_error_span() {
+        let input = "let f = fn(x) {\n  x + true;\n};\nf(1);";
+        let mut vm = Vm::new(&compile(&parse(input).unwrap()).unwrap());
+        let res = vm.run();
+        assert_eq!(res, Object::Error("type mismatch: INTEGER + BOOLEAN".into()));
+        let span = vm.error_span().unwrap();
+        assert_eq!(&input[span.as_range()], "x + true;");
+        assert_eq!(vm.run(), Object::Null);
+    }
+
+    #[test]
+    fn run_invalid_bytecode() {
+        let mut bytecode = compile(&parse("1 + 2;").unwrap()).unwrap();
+        Rc::get_mut(&mut bytecode.main).unwrap().instructions[1] = 9;
+        assert_eq!(
+            run(&bytecode),
+            Object::Error("invalid bytecode: bad constant".into())
+        );
+    }
+}
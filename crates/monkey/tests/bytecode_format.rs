@@ -0,0 +1,55 @@
+//! Loads corrupted bytecode files. Loading has to fail with an error or give bytecode that the vm
+//! can run, it must never panic.
+
+use monkey::compiler::{compile, Bytecode};
+use monkey::parser::parse;
+use monkey::vm;
+use monkey::Object;
+use proptest::prelude::*;
+
+const PROGRAM: &str = r#"
+let total = 0;
+let add = fn(x) { total += x; total };
+let pair = fn(a) { fn(b) { [a, b, {"sum": a + b}] } };
+if (len("abc") > 2) { add(1.5) } else { add(2) }
+pair(1)(add(3))[2]["sum"];
+"#;
+
+fn program_bytes() -> Vec<u8> {
+    let bytecode = compile(&parse(PROGRAM).unwrap()).unwrap();
+    let mut bytes = Vec::new();
+    bytecode.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn loaded_bytecode_runs_like_the_compiled_one() {
+    let bytes = program_bytes();
+    let loaded = Bytecode::read_from(&mut &bytes[..]).unwrap();
+    assert_eq!(vm::run(&loaded), Object::Float(5.5));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2_000))]
+
+    #[test]
+    fn corrupted_bytes_do_not_panic(
+        changes in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8)
+    ) {
+        let mut bytes = program_bytes();
+        for (idx, byte) in changes {
+            let idx = idx.index(bytes.len());
+            bytes[idx] = byte;
+        }
+        if let Ok(bytecode) = Bytecode::read_from(&mut &bytes[..]) {
+            let _ = vm::run(&bytecode);
+        }
+    }
+
+    #[test]
+    fn truncated_files_are_rejected(len in 0usize..200) {
+        let bytes = program_bytes();
+        let len = len.min(bytes.len() - 1);
+        prop_assert!(Bytecode::read_from(&mut &bytes[..len]).is_err());
+    }
+}
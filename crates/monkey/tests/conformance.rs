@@ -0,0 +1,248 @@
+//! Runs every program through both the evaluator and the compiler and vm, which have to agree on
+//! the result, or on the error and the statement it happened in.
+
+use monkey::compiler::compile;
+use monkey::eval::{Environment, Evaluator, STACK_SIZE};
+use monkey::parser::parse;
+use monkey::vm::Vm;
+use monkey::Object;
+use proptest::prelude::*;
+
+/// Programs covering every kind of expression, and the errors they can run into
+const PROGRAMS: &[&str] = &[
+    // literals and operators
+    "1 + 2 * 3 - 4 / 2;",
+    "-5 + 10 % 3;",
+    "1.5 * 2 + 1;",
+    "7.5 % 2;",
+    "!true == !!false;",
+    "1 < 2 == 2 > 1;",
+    "1 <= 1 && 2 >= 3;",
+    r#""a" + "b" == "ab";"#,
+    r#""a" != "b";"#,
+    "0x10 + 0b1 + 0o7 + 1_000;",
+    "1.0 / 0;",
+    // logical operators short circuit and give booleans
+    "1 && 2;",
+    "0 || false;",
+    "false && undefined;",
+    "true || 1 / 0;",
+    "null_value && 1;",
+    "let x = 1; false && (x = 2); true || (x = 3); x;",
+    // conditionals
+    "if (true) { 10 }",
+    "if (false) { 10 }",
+    "if (1 > 2) { 10 } else { 20 }",
+    "if (1) { let a = 5; }",
+    "if (true) {}",
+    "let x = if (false) { 1 }; x;",
+    "if (1 > 2) { 10 } else if (2 > 1) { 20 } else { 30 }",
+    "let x = 3; if (x == 1) { 10 } else if (x == 2) { 20 } else if (x == 3) { 30 }",
+    // globals and assignment
+    "let a = 5; let b = a * 2; a + b;",
+    "let a = 5;",
+    "let a = 1; let a = a + 1; a;",
+    "let x = 10; x += 5; x -= 1; x *= 2; x /= 4; x %= 4; x;",
+    "let x = 1; let y = 2; x = y = 3; x + y;",
+    r#"let s = "a"; s += "b"; s;"#,
+    "if (true) { let inner = 3; } inner;",
+    // collections
+    "[1, 2 * 2, 3 + 3];",
+    "[1, [2, 3]][1][0];",
+    "[1, 2][2];",
+    "[1, 2][-1];",
+    r#"{"b": 2, "a": 1, true: [], 3: {}};"#,
+    r#"let k = "b"; {"a" + k: 5}["ab"];"#,
+    "{1: 1, 1: 2}[1];",
+    "{}[true];",
+    // functions
+    "let identity = fn(x) { x }; identity(5);",
+    "let f = fn(x) { return x * 2; 100 }; f(3);",
+    "let f = fn() {}; f();",
+    "let f = fn() { let a = 1; }; f();",
+    "fn(a, b) { a - b }(10, 3);",
+    "let add = fn(a, b) { a + b }; add(1, add(2, 3));",
+    "let f = fn(x) { if (x) { return 1; } 2 }; f(true) + f(false);",
+    "let f = fn(x, x) { x }; f(1, 2);",
+    "let x = 1; let f = fn(x) { x = 5 }; f(2); x;",
+    "let f = fn() { g() }; let g = fn() { 7 }; f();",
+    "return 10; 9;",
+    "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+    // closures
+    "let adder = fn(x) { fn(y) { x + y } }; adder(2)(3);",
+    "let n = 0; let inc = fn() { n += 1 }; inc(); inc(); n;",
+    "let counter = fn() { let n = 0; fn() { n += 1; n } }; let c = counter(); c(); c(); c();",
+    "let c = fn() { let n = 0; [fn() { n += 1 }, fn() { n }] }(); c[0](); c[0](); c[1]();",
+    "let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3);",
+    "let f = fn() { let x = 1; let g = fn() { x }; let x = 2; g() }; f();",
+    "let f = fn() { let g = fn(n) { if (n == 0) { 0 } else { n + g(n - 1) } }; g(10) }; f();",
+    "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);",
+    "let x = 1; let f = fn() { let x = 2; fn() { x } }; f()() + x;",
+    "let x = 1; let f = fn(x) { let x = x * 10; fn() { x } }; let g = f(2); let x = 5; g() + x;",
+    "let counter = fn() { let n = 0; fn() { n += 1; n } }; let a = counter(); let b = counter(); a(); [a(), b()];",
+    "let curry = fn(f) { fn(x) { fn(y) { f(x, y) } } }; curry(fn(a, b) { a - b })(10)(3);",
+    "let twice = fn(f) { fn(x) { f(f(x)) } }; twice(twice(fn(x) { x * 2 }))(1);",
+    "let reduce = fn(a, acc, f) { if (len(a) == 0) { acc } else { reduce(rest(a), f(acc, first(a)), f) } }; reduce([1, 2, 3], 0, fn(s, x) { s + x });",
+    "let range = fn(n, a) { if (n == 0) { a } else { range(n - 1, push(a, n)) } }; let reduce = fn(a, acc, f) { if (len(a) == 0) { acc } else { reduce(rest(a), f(acc, first(a)), f) } }; reduce(range(200, []), 0, fn(s, x) { s + x });",
+    // names are looked up when they are used, in the innermost scope that has them
+    "let f = fn() { let g = fn() { h() }; let h = fn() { 1 }; g() }; f();",
+    "let f = fn(n) { let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; even(n) }; f(7);",
+    "let x = 1; let f = fn() { let g = fn() { x }; let x = 2; g() }; f();",
+    "let x = 1; let f = fn() { let g = fn() { x }; let y = g(); let x = 2; [y, g()] }; f();",
+    "let x = 1; let f = fn() { let x = x + 1; x }; [f(), x];",
+    "let x = 1; let f = fn() { x = 5; let x = 2; x = 3; x }; [f(), x];",
+    "let f = fn() { let x = [fn() { x }]; len(x[0]()) }; f();",
+    "let f = fn(c) { if (c) { let y = 1; } fn() { y } }; let y = 2; [f(true)(), f(false)()];",
+    "let f = fn() { y }; f();",
+    "len = 5; len;",
+    "let f = fn() { len += 1 }; f();",
+    "let len = 5; len = 6; len;",
+    // builtins
+    r#"len("four") + len([1, 2]) + len({1: 2});"#,
+    "first([1, 2]) + last([1, 2]);",
+    "rest([1, 2, 3]);",
+    "let a = [1]; push(a, 2); a;",
+    "type_of(len) + type_of(fn() {}) + type_of(1.5);",
+    r#"str(1) + str([true, "a"]);"#,
+    r#"int(" -42 ") + int(2.9) + int(true);"#,
+    "let len = fn(x) { 5 }; len([]);",
+    "let f = fn() { len([1]) }; let len = fn(x) { 5 }; f();",
+    // errors
+    "5 + true;",
+    "5; true + false; 5;",
+    "-true;",
+    r#""a" - "b";"#,
+    "foobar;",
+    "5 / 0;",
+    "1 % 0;",
+    "9223372036854775807 + 1;",
+    "-(-9223372036854775807 - 1);",
+    "5(1);",
+    "fn(x) { x }();",
+    "let f = fn(x) { y }; f(1);",
+    "let x = -true; 5;",
+    "y = 1;",
+    "y += 1;",
+    "let x = true; x += 1;",
+    "1[0];",
+    "{}[[]];",
+    "{fn() {}: 1};",
+    "{[]: undefined};",
+    r#"len("one", "two");"#,
+    "len(1);",
+    r#"int("4x");"#,
+    "let f = fn() {\n  let x = 1;\n  if (x) {\n    x + len;\n  }\n};\nf();",
+    "let f = fn() { [1, fn() { 1 / 0 }()] }; f();",
+    "let f = fn(x) { f(x + 1) }; f(0);",
+    "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(9999);",
+    "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(10000);",
+];
+
+/// The value or error of a program, with the text of the statement that failed
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Value(String),
+    Error(String, Option<String>),
+}
+
+fn outcome(input: &str, value: Object, span: Option<monkey::common::Span>) -> Outcome {
+    match value {
+        Object::Error(message) => {
+            let statement = span.map(|span| input[span.as_range()].to_string());
+            Outcome::Error(message, statement)
+        }
+        // the two kinds of functions print differently
+        value if value.type_name() == "FUNCTION" => Outcome::Value("FUNCTION".into()),
+        value => Outcome::Value(value.to_string()),
+    }
+}
+
+/// Runs the program through both backends, the evaluator's outcome comes first
+fn run_both(input: &str) -> (Outcome, Outcome) {
+    let program = parse(input).unwrap();
+
+    let mut evaluator = Evaluator::new().with_stack_size(STACK_SIZE);
+    let value = evaluator.eval_program(&program, &Environment::new());
+    let evaluated = outcome(input, value, evaluator.error_span());
+
+    let bytecode = compile(&program).unwrap();
+    let mut vm = Vm::new(&bytecode);
+    let value = vm.run();
+    let ran = outcome(input, value, vm.error_span());
+
+    (evaluated, ran)
+}
+
+#[test]
+fn backends_agree() {
+    // the deepest recursion needs more than the stack of a test thread
+    let programs = std::thread::Builder::new().stack_size(STACK_SIZE).spawn(|| {
+        for input in PROGRAMS {
+            let (evaluated, ran) = run_both(input);
+            assert_eq!(evaluated, ran, "input: {}", input);
+        }
+    });
+    programs.unwrap().join().unwrap();
+}
+
+#[test]
+fn errors_point_at_the_innermost_statement() {
+    let input = PROGRAMS.iter().find(|input| input.contains("x + len")).unwrap();
+    let (evaluated, _) = run_both(input);
+    assert_eq!(
+        evaluated,
+        Outcome::Error(
+            "type mismatch: INTEGER + BUILTIN".into(),
+            Some("x + len;".into())
+        )
+    );
+}
+
+/// Whole statements, so that most generated programs parse
+const STATEMENTS: &[&str] = &[
+    "let x = 1;",
+    "let x = \"s\";",
+    "let y = [x, 2];",
+    "x += 2;",
+    "x = y;",
+    "y[0];",
+    "let f = fn(a) { a * 2 };",
+    "let f = fn(a) { x = a; y };",
+    "let g = fn() { let x = 5; fn() { x += 1; x } };",
+    "let h = g();",
+    "h();",
+    "f(x);",
+    "f(h());",
+    "if (x > 2) { x } else { -x }",
+    "if (x > 2) { x } else if (y) { f(y) } else { -x }",
+    "if (y) { let z = x; }",
+    "z;",
+    "return x;",
+    "{\"a\": x, x: y};",
+    "len(y);",
+    "push(y, f);",
+    "x / 0;",
+    "true && x;",
+    "false || y;",
+    "let r = fn(n) { if (n < 1) { 0 } else { n + r(n - 1) } };",
+    "r(x);",
+    "let x = r;",
+    "let k = fn() { let v = x; let x = [v]; x };",
+    "k();",
+    "let s = fn() { let a = fn() { b() }; if (x) { let b = fn() { x }; } a };",
+    "s()();",
+    "len = x;",
+];
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1_000))]
+
+    #[test]
+    fn generated_programs_agree(
+        statements in prop::collection::vec(prop::sample::select(STATEMENTS), 0..16)
+    ) {
+        let input = statements.join("\n");
+        let (evaluated, ran) = run_both(&input);
+        prop_assert_eq!(evaluated, ran, "input: {}", input);
+    }
+}
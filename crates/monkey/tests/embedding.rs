@@ -0,0 +1,202 @@
+//! Uses the crate the way a host application would, through `Interpreter` only.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use monkey::common::SourceMap;
+use monkey::parser::parse;
+use monkey::{Interpreter, InterpreterError, Object, RuntimeError};
+
+#[test]
+fn state_is_kept_across_calls() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("let x = 1;").unwrap();
+    interpreter.eval_str("let add = fn(a, b) { a + b };").unwrap();
+    assert_eq!(interpreter.eval_str("add(x, 2);").unwrap(), Object::Integer(3));
+    interpreter.eval_str("x += 10;").unwrap();
+    assert_eq!(interpreter.get_global("x"), Some(Object::Integer(11)));
+}
+
+#[test]
+fn globals() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("name", "web");
+    interpreter.set_global("ports", vec![80, 443]);
+    interpreter.set_global("debug", true);
+    assert_eq!(
+        interpreter.eval_str(r#"if (debug) { name + ":" + str(ports[1]) }"#).unwrap(),
+        Object::String("web:443".into())
+    );
+    assert_eq!(interpreter.get_global("missing"), None);
+}
+
+#[test]
+fn call_function() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval_str("let total = 0; let count = fn(x) { total += x };")
+        .unwrap();
+    interpreter.call_function("count", vec![2.into()]).unwrap();
+    interpreter.call_function("count", vec![3.into()]).unwrap();
+    assert_eq!(interpreter.get_global("total"), Some(Object::Integer(5)));
+
+    assert_eq!(
+        interpreter.call_function("len", vec!["abc".into()]).unwrap(),
+        Object::Integer(3)
+    );
+}
+
+#[test]
+fn errors() {
+    let mut interpreter = Interpreter::new();
+    match interpreter.eval_str("let = 1; let y 2;") {
+        Err(InterpreterError::Parse { first, rest }) => {
+            assert_eq!(first.node.to_string(), "Expected identifier, got `=`");
+            assert_eq!(rest.len(), 1);
+        }
+        other => panic!("expected parse errors, got {:?}", other),
+    }
+
+    let source = "let f = fn(x) { x + true };\nf(1);";
+    match interpreter.eval_str(source) {
+        Err(InterpreterError::Runtime {
+            message,
+            span: Some(span),
+            ..
+        }) => {
+            assert_eq!(message, "type mismatch: INTEGER + BOOLEAN");
+            assert_eq!(&source[span.as_range()], "x + true");
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+
+    let err = interpreter.call_function("nope", vec![]).unwrap_err();
+    assert_eq!(err.to_string(), "identifier not found: nope");
+    let err = interpreter.call_function("f", vec![]).unwrap_err();
+    assert_eq!(err.to_string(), "wrong number of arguments: expected 1, got 0");
+
+    let err = interpreter.eval_file("does/not/exist.mk").unwrap_err();
+    assert!(matches!(err, InterpreterError::Io { .. }), "{:?}", err);
+}
+
+#[test]
+fn errors_name_the_file_of_the_function() {
+    let mut map = SourceMap::new();
+    let lib = map.add("lib.mk", "let f = fn(x) { x + true };");
+    let main = map.add("main.mk", "let y = 1;\nf(y);");
+    let mut interpreter = Interpreter::new();
+    let program = |file| parse(map.get(file).source()).unwrap();
+    interpreter.eval_program_in(lib, &program(lib)).unwrap();
+    match interpreter.eval_program_in(main, &program(main)) {
+        Err(InterpreterError::Runtime {
+            span: Some(span),
+            file: Some(file),
+            ..
+        }) => {
+            assert_eq!(file, lib);
+            assert_eq!(&map.get(file).source()[span.as_range()], "x + true");
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn runaway_recursion_is_an_error() {
+    // evaluated on the stack of the test thread, which must not overflow
+    let mut interpreter = Interpreter::new();
+    for source in &[
+        "let f = fn(x) { f(x + 1) }; f(0);",
+        "let f = fn(n) { 1 + f(n + 1) }; f(0);",
+    ] {
+        match interpreter.eval_str(source) {
+            Err(InterpreterError::Runtime { message, .. }) => {
+                assert_eq!(message, "stack overflow: too many nested function calls")
+            }
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+    // the interpreter is still usable afterwards
+    assert_eq!(interpreter.eval_str("f;").unwrap().type_name(), "FUNCTION");
+}
+
+#[test]
+fn eval_file() {
+    let path = std::env::temp_dir().join("monkey-embedding-eval-file.mk");
+    let mut file = std::fs::File::create(&path).unwrap();
+    writeln!(file, "let config = {{\"retries\": 3}};").unwrap();
+    drop(file);
+
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_file(&path).unwrap();
+    assert_eq!(
+        interpreter.eval_str(r#"config["retries"];"#).unwrap(),
+        Object::Integer(3)
+    );
+}
+
+#[test]
+fn native_functions() {
+    let logged = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    let log = logged.clone();
+    interpreter.register_fn("log", move |args: &[Object]| {
+        let line: Vec<_> = args.iter().map(ToString::to_string).collect();
+        log.borrow_mut().push(line.join(" "));
+        Ok(Object::Null)
+    });
+    interpreter.register_typed_fn("lookup", |id: i64| match id {
+        1 => Ok("alice".to_string()),
+        _ => Err(RuntimeError::new(format!("no user with id {}", id))),
+    });
+    interpreter.register_typed_fn("sum", |xs: Vec<i64>| Ok(xs.iter().sum::<i64>()));
+
+    assert_eq!(
+        interpreter
+            .eval_str(r#"log("user", lookup(1)); sum([1, 2, 3]);"#)
+            .unwrap(),
+        Object::Integer(6)
+    );
+    assert_eq!(*logged.borrow(), ["user alice"]);
+    assert_eq!(
+        interpreter.eval_str("type_of(log);").unwrap(),
+        Object::String("NATIVE_FUNCTION".into())
+    );
+    assert_eq!(
+        interpreter.call_function("lookup", vec![1.into()]).unwrap(),
+        Object::String("alice".into())
+    );
+}
+
+#[test]
+fn native_errors_point_at_the_call() {
+    let mut interpreter = Interpreter::new();
+    interpreter.register_fn("fail", |_: &[Object]| Err("database is down".into()));
+    interpreter.register_typed_fn("double", |x: i64| Ok(x * 2));
+
+    let source = "let f = fn() {\n  let x = 1;\n  fail(x);\n};\nf();";
+    match interpreter.eval_str(source) {
+        Err(InterpreterError::Runtime {
+            message,
+            span: Some(span),
+            ..
+        }) => {
+            assert_eq!(message, "database is down");
+            assert_eq!(&source[span.as_range()], "fail(x)");
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+
+    let source = "let y = [1, 2 + double(fail(3))];";
+    match interpreter.eval_str(source) {
+        Err(InterpreterError::Runtime {
+            span: Some(span), ..
+        }) => assert_eq!(&source[span.as_range()], "fail(3)"),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+
+    let err = interpreter.eval_str(r#"double("2");"#).unwrap_err();
+    assert_eq!(err.to_string(), "argument 1 to `double`: expected INTEGER, got STRING");
+    let err = interpreter.eval_str("double(1, 2);").unwrap_err();
+    assert_eq!(err.to_string(), "wrong number of arguments: expected 1, got 2");
+}
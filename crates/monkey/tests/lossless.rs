@@ -0,0 +1,51 @@
+//! Property tests for the lossless lexer: the text of its tokens put together has to be the input,
+//! and leaving out the trivia has to give the same tokens as the normal lexer.
+
+use monkey::lexer::Lexer;
+use proptest::prelude::*;
+
+/// Fragments that are interesting next to each other, they are joined without separators
+const FRAGMENTS: &[&str] = &[
+    "let", "x", "1", "1.5", "1e", "\"s\"", "\"", "\\", "=", "/", "*", "//", "///", "////", "/*",
+    "*/", " ", "\t", "\n", "\r\n", "\r", "\u{a0}", "$", "🐒", "comment",
+];
+
+fn fragments() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(FRAGMENTS), 0..64)
+        .prop_map(|fragments| fragments.concat())
+}
+
+fn check(input: &str) -> Result<(), TestCaseError> {
+    let tokens: Vec<_> = Lexer::lossless(input).collect();
+
+    let text: String = tokens.iter().map(|token| token.node.as_str()).collect();
+    prop_assert_eq!(&text, input);
+
+    let mut end = 0;
+    for token in &tokens {
+        prop_assert_eq!(token.span.start, end);
+        prop_assert_eq!(&input[token.span.as_range()], token.node.as_str());
+        end = token.span.end;
+    }
+
+    let significant: Vec<_> = tokens
+        .into_iter()
+        .filter(|token| !token.node.is_trivia())
+        .collect();
+    prop_assert_eq!(significant, Lexer::new(input).collect::<Vec<_>>());
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2_000))]
+
+    #[test]
+    fn arbitrary_strings_round_trip(input in any::<String>()) {
+        check(&input)?;
+    }
+
+    #[test]
+    fn token_soup_round_trips(input in fragments()) {
+        check(&input)?;
+    }
+}
@@ -0,0 +1,112 @@
+//! Property tests that feed arbitrary input to the lexer and parser. Neither of them should ever
+//! panic, no matter how malformed the input is.
+
+use monkey::ast::pretty;
+use monkey::compiler::compile;
+use monkey::lexer::Lexer;
+use monkey::parser::{parse, parse_with_errors, MAX_DEPTH};
+use proptest::prelude::*;
+
+/// Fragments of valid syntax, so that the generated input gets further into the parser than
+/// random characters would
+const FRAGMENTS: &[&str] = &[
+    "let", "return", "fn", "if", "else", "true", "false", "x", "y", "add", "0", "5", "1234",
+    "99999999999999999999", "1.5", ".", "=", "==", "!=", "+", "-", "*", "/", "!", "<", ">", "<=",
+    ">=", ",", ";", "(", ")", "{", "}", "//", "\n", " ", "$", "Здравствуйте", "\"s\"", "\"",
+    "\\", "\\n", "\\u{41}", "\\u{D800}", "1e5", "2.5e-3", "0x1f", "0b2", "1_000", "1e999",
+    "/*", "*/", "///", "/// doc\n", "[", "]", ":", "{\"a\": 1}", "[1, 2]", "x[0]",
+    "&&", "||", "&", "%", "+=", "-=", "%=", "x = 1",
+];
+
+fn fragments() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(FRAGMENTS), 0..64)
+        .prop_map(|fragments| fragments.join(" "))
+}
+
+fn check(input: &str) {
+    let tokens: Vec<_> = Lexer::new(input).collect();
+    for token in &tokens {
+        assert!(token.span.start <= token.span.end);
+        assert!(input.get(token.span.as_range()).is_some());
+    }
+
+    let (program, errors) = parse_with_errors(input);
+    let _ = program.to_string();
+    for err in &errors {
+        let _ = err.node.to_string();
+        assert!(err.span.end <= input.len());
+    }
+    assert_eq!(parse(input).is_ok(), errors.is_empty());
+}
+
+/// Ways to nest, as the text before and after the innermost expression
+const NESTING: &[(&str, &str, &str)] = &[
+    ("(", "x", ")"),
+    ("-", "x", ""),
+    ("[", "x", "]"),
+    ("{1: ", "x", "}"),
+    ("f(", "x", ")"),
+    ("x[", "0", "]"),
+    ("x = ", "1", ""),
+    ("fn() {", "x", "}"),
+    ("fn() {", "x", "}()"),
+    ("if (x) {", "x", "}"),
+    ("if (x) {} else ", "{ x }", ""),
+];
+
+#[test]
+fn deepest_nesting_fits_the_stack_of_a_test_thread() {
+    for (open, inner, close) in NESTING {
+        let nested = format!("{}{}{}", open.repeat(MAX_DEPTH), inner, close.repeat(MAX_DEPTH));
+        let input = format!("let x = 1; let f = fn(x) {{ x }}; {};", nested);
+        let program = parse(&input).unwrap();
+        let printed = pretty(&program);
+        let reparsed = parse(&printed).map(|program| program.to_string());
+        assert_eq!(reparsed, Ok(program.to_string()));
+        compile(&program).map(|bytecode| monkey::vm::run(&bytecode)).unwrap();
+        monkey::eval::eval(&program);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2_000))]
+
+    #[test]
+    fn arbitrary_strings_do_not_panic(input in any::<String>()) {
+        check(&input);
+    }
+
+    #[test]
+    fn token_soup_does_not_panic(input in fragments()) {
+        check(&input);
+    }
+
+    #[test]
+    fn deep_nesting_does_not_panic(
+        depth in 0usize..5_000,
+        open in prop::sample::select(
+            &["(", "-", "!", "[", "{1: ", "fn() {", "if x {", "if x {} else ", "f("][..]
+        ),
+    ) {
+        check(&open.repeat(depth));
+    }
+
+    #[test]
+    fn printed_programs_reparse(input in fragments()) {
+        if let Ok(program) = parse(&input) {
+            let printed = program.to_string();
+            // spans differ after printing, so compare the printed forms
+            let reparsed = parse(&printed).map(|program| program.to_string());
+            prop_assert_eq!(reparsed.map_err(|err| err.node), Ok(printed));
+        }
+    }
+
+    #[test]
+    fn pretty_programs_reparse(input in fragments()) {
+        if let Ok(program) = parse(&input) {
+            let printed = pretty(&program);
+            let reparsed = parse(&printed).map(|program| program.to_string());
+            prop_assert_eq!(reparsed.map_err(|err| err.node), Ok(program.to_string()));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn monkey(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monkey"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("monkey-cli-{}-{}", std::process::id(), name));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn run_file() {
+    let path = script("ok.mk", "let x = 2;\nx * 21;\n");
+    let output = monkey(&[path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "42\n");
+}
+
+#[test]
+fn run_stdin() {
+    let output = monkey(&["-"], "let x = 2; x * 21;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "42\n");
+}
+
+#[test]
+fn parse_error() {
+    let path = script("parse.mk", "let x = 5;\nlet = 3;\n");
+    let output = monkey(&[path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    let expected = format!("{}:2:5", path.display());
+    assert!(stderr(&output).contains(&expected), "{}", stderr(&output));
+}
+
+#[test]
+fn runtime_error() {
+    let output = monkey(&["-"], "let f = fn(x) {\n  x + true;\n};\nf(1);");
+    assert_eq!(output.status.code(), Some(70));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("error: type mismatch: INTEGER + BOOLEAN"), "{}", stderr);
+    assert!(stderr.contains("<stdin>:2:3"), "{}", stderr);
+}
+
+#[test]
+fn columns_count_chars() {
+    let output = monkey(&["check"], "let Здравствуйте = 1;\nlet café = ;\n");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("<stdin>:2:12"), "{}", stderr(&output));
+
+    let output = monkey(&["-"], "let é = 1; if (é) { é + true; }");
+    assert!(stderr(&output).contains("<stdin>:1:21"), "{}", stderr(&output));
+
+    let output = monkey(&["lex"], "\"ü\" x");
+    assert!(stdout(&output).contains("<stdin>:1:5\tIdent(\"x\")"), "{}", stdout(&output));
+}
+
+#[test]
+fn io_error() {
+    let output = monkey(&["does-not-exist.mk"], "");
+    assert_eq!(output.status.code(), Some(74));
+    assert!(stderr(&output).contains("could not read `does-not-exist.mk`"));
+}
+
+#[test]
+fn subcommands_read_stdin() {
+    let output = monkey(&["lex"], "let x");
+    assert_eq!(stdout(&output), "<stdin>:1:1\tLet\n<stdin>:1:5\tIdent(\"x\")\n");
+
+    let output = monkey(&["lex", "--lossless"], "x // y");
+    assert_eq!(
+        stdout(&output),
+        "<stdin>:1:1\tIdent(\"x\")\n<stdin>:1:2\tWhitespace(\" \")\n<stdin>:1:3\tComment(\"// y\")\n"
+    );
+
+    let output = monkey(&["check"], "let x = 1;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+
+    let output = monkey(&["run"], "1 + 2;");
+    assert_eq!(stdout(&output), "3\n");
+
+    let output = monkey(&["run"], "puts(\"a\", [1]); len(\"abc\");");
+    assert_eq!(stdout(&output), "a\n[1]\n3\n");
+
+    let output = monkey(&["parse"], "x;");
+    assert!(stdout(&output).contains("Identifier("), "{}", stdout(&output));
+}
+
+#[test]
+fn check_reports_every_file() {
+    let good = script("check-good.mk", "let x = 1;\n");
+    let bad = script("check-bad.mk", "let = 1;\n");
+    let output = monkey(&["check", bad.to_str().unwrap(), good.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains(&format!("{}:1:5", bad.display())));
+}
+
+#[test]
+fn fmt() {
+    let output = monkey(&["fmt"], "let f=fn(x){(x+1)*2};");
+    assert_eq!(stdout(&output), "let f = fn(x) {\n    (x + 1) * 2;\n};\n");
+
+    let output = monkey(&["fmt", "--check"], "let x=1;");
+    assert_eq!(output.status.code(), Some(1));
+    let output = monkey(&["fmt", "--check"], "let x = 1;\n");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = monkey(&["fmt"], "// note\nlet x=1; /* keep */\n/// doc\nx;");
+    assert_eq!(stdout(&output), "// note\nlet x = 1; /* keep */\n/// doc\nx;\n");
+
+    let output = monkey(&["fmt"], "let x = [1, // one\n2];");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("Comments inside of expressions cannot be formatted"));
+}
+
+#[test]
+fn json_format() {
+    let output = monkey(&["lex", "--format", "json"], "x");
+    assert_eq!(
+        stdout(&output),
+        "{\"file\":\"<stdin>\",\"tokens\":[{\"kind\":\"Ident\",\"text\":\"x\",\"span\":{\"start\":0,\"end\":1}}]}\n"
+    );
+
+    let output = monkey(&["check", "--format", "json"], "let = 1;");
+    assert_eq!(output.status.code(), Some(65));
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["diagnostics"][0]["message"], "Expected identifier, got `=`");
+    assert_eq!(json["diagnostics"][0]["labels"][0]["start"]["col"], 5);
+
+    let output = monkey(&["parse", "--format", "json"], "x;");
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["program"]["statements"][0]["data"]["kind"], "Identifier");
+    assert_eq!(json["diagnostics"], serde_json::json!([]));
+}
+
+#[test]
+fn disasm() {
+    let output = monkey(&["disasm"], "let x = 1;\nputs(x + 2);\n");
+    assert_eq!(output.status.code(), Some(0));
+    let listing = stdout(&output);
+    assert!(listing.starts_with("== <main> ==\n        1 | let x = 1;\n"), "{}", listing);
+    assert!(listing.contains("0003 SetGlobal     0  ; x\n        2 | puts(x + 2);\n"), "{}", listing);
+
+    let output = monkey(&["disasm"], "let = 1;");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn compile_and_run_bytecode() {
+    let source = script("compiled.mk", "let f = fn(x) {\n  x * 2\n};\nputs(f(2));\nf(21);\n");
+    let output_path = source.with_extension("mkc");
+    let output = monkey(
+        &["compile", "-o", output_path.to_str().unwrap(), source.to_str().unwrap()],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    let output = monkey(&["run", output_path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "4\n42\n");
+
+    let failing = script("failing.mkc", "");
+    let source = "let f = fn() {\n  1 / 0;\n};\nf();";
+    let output = monkey(&["compile", "-o", failing.to_str().unwrap(), "-"], source);
+    assert_eq!(output.status.code(), Some(0));
+    let output = monkey(&["run", failing.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr(&output).contains("division by zero"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("at bytes 17..23"), "{}", stderr(&output));
+}
+
+#[test]
+fn invalid_bytecode() {
+    let path = script("invalid.mkc", "let x = 1;");
+    let output = monkey(&["run", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("Not a Monkey bytecode file"), "{}", stderr(&output));
+
+    let mut bytes = b"MKC\0\0\x09".to_vec();
+    bytes.extend(&[0; 12]);
+    std::fs::write(&path, bytes).unwrap();
+    let output = monkey(&["run", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("version 9 is not supported"), "{}", stderr(&output));
+}
+
+#[test]
+fn repl_errors_point_at_earlier_lines() {
+    let dir = std::env::temp_dir().join(format!("monkey-cli-{}-repl", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monkey"))
+        .arg("repl")
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = "let f = fn(x) { x + true };\nlet xééééééééé = f(1);\n";
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(
+        stderr(&output),
+        "\
+error: type mismatch: INTEGER + BOOLEAN
+ --> <repl>:1:17
+  |
+1 | let f = fn(x) { x + true };
+  |                 ^^^^^^^^
+"
+    );
+}